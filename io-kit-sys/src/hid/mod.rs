@@ -1,7 +1,12 @@
+#[cfg(target_os = "macos")]
 pub mod base;
+#[cfg(target_os = "macos")]
 pub mod device;
+#[cfg(target_os = "macos")]
 pub mod element;
 pub mod keys;
+#[cfg(target_os = "macos")]
 pub mod manager;
 pub mod usage_tables;
+#[cfg(target_os = "macos")]
 pub mod value;
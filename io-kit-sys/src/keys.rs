@@ -190,3 +190,41 @@ pub const kIOBootDeviceSizeKey: *const ::std::os::raw::c_char =
 // keys for OS Version information
 pub const kOSBuildVersionKey: *const ::std::os::raw::c_char =
     b"OS Build Version\x00" as *const [u8; 17usize] as *const ::std::os::raw::c_char;
+
+// exports from <IOKit/IOBSD.h>
+
+pub const kIOBSDNameKey: *const ::std::os::raw::c_char =
+    b"BSD Name\x00" as *const [u8; 9usize] as *const ::std::os::raw::c_char;
+pub const kIOBSDNamesKey: *const ::std::os::raw::c_char =
+    b"BSD Names\x00" as *const [u8; 10usize] as *const ::std::os::raw::c_char;
+pub const kIOBSDMajorKey: *const ::std::os::raw::c_char =
+    b"BSD Major\x00" as *const [u8; 10usize] as *const ::std::os::raw::c_char;
+pub const kIOBSDMinorKey: *const ::std::os::raw::c_char =
+    b"BSD Minor\x00" as *const [u8; 10usize] as *const ::std::os::raw::c_char;
+pub const kIOBSDUnitKey: *const ::std::os::raw::c_char =
+    b"BSD Unit\x00" as *const [u8; 9usize] as *const ::std::os::raw::c_char;
+
+// exports from <IOKit/storage/IOMedia.h>
+
+pub const kIOMediaClass: *const ::std::os::raw::c_char =
+    b"IOMedia\x00" as *const [u8; 8usize] as *const ::std::os::raw::c_char;
+pub const kIOMediaContentKey: *const ::std::os::raw::c_char =
+    b"Content\x00" as *const [u8; 8usize] as *const ::std::os::raw::c_char;
+pub const kIOMediaContentHintKey: *const ::std::os::raw::c_char =
+    b"Content Hint\x00" as *const [u8; 13usize] as *const ::std::os::raw::c_char;
+pub const kIOMediaEjectableKey: *const ::std::os::raw::c_char =
+    b"Ejectable\x00" as *const [u8; 10usize] as *const ::std::os::raw::c_char;
+pub const kIOMediaLeafKey: *const ::std::os::raw::c_char =
+    b"Leaf\x00" as *const [u8; 5usize] as *const ::std::os::raw::c_char;
+pub const kIOMediaPreferredBlockSizeKey: *const ::std::os::raw::c_char =
+    b"Preferred Block Size\x00" as *const [u8; 21usize] as *const ::std::os::raw::c_char;
+pub const kIOMediaRemovableKey: *const ::std::os::raw::c_char =
+    b"Removable\x00" as *const [u8; 10usize] as *const ::std::os::raw::c_char;
+pub const kIOMediaSizeKey: *const ::std::os::raw::c_char =
+    b"Size\x00" as *const [u8; 5usize] as *const ::std::os::raw::c_char;
+pub const kIOMediaUUIDKey: *const ::std::os::raw::c_char =
+    b"UUID\x00" as *const [u8; 5usize] as *const ::std::os::raw::c_char;
+pub const kIOMediaWholeKey: *const ::std::os::raw::c_char =
+    b"Whole\x00" as *const [u8; 6usize] as *const ::std::os::raw::c_char;
+pub const kIOMediaWritableKey: *const ::std::os::raw::c_char =
+    b"Writable\x00" as *const [u8; 9usize] as *const ::std::os::raw::c_char;
@@ -0,0 +1,44 @@
+// exports from <IOKit/IOMessage.h>
+
+// sys_iokit
+const SYS_IOKIT: u32 = ((0x38) & 0x3f) << 26;
+const SUB_IOKIT_COMMON: u32 = ((0) & 0xfff) << 14;
+
+pub type IOMessage = u32;
+
+pub const kIOMessageServiceIsTerminated: IOMessage = SYS_IOKIT | SUB_IOKIT_COMMON | 0x010;
+pub const kIOMessageServiceIsSuspended: IOMessage = SYS_IOKIT | SUB_IOKIT_COMMON | 0x020;
+pub const kIOMessageServiceIsResumed: IOMessage = SYS_IOKIT | SUB_IOKIT_COMMON | 0x030;
+
+pub const kIOMessageServiceIsRequestingClose: IOMessage = SYS_IOKIT | SUB_IOKIT_COMMON | 0x100;
+pub const kIOMessageServiceIsAttemptingOpen: IOMessage = SYS_IOKIT | SUB_IOKIT_COMMON | 0x101;
+pub const kIOMessageServiceWasClosed: IOMessage = SYS_IOKIT | SUB_IOKIT_COMMON | 0x110;
+
+pub const kIOMessageServiceBusyStateChange: IOMessage = SYS_IOKIT | SUB_IOKIT_COMMON | 0x120;
+
+pub const kIOMessageConsoleSecurityChange: IOMessage = SYS_IOKIT | SUB_IOKIT_COMMON | 0x128;
+
+pub const kIOMessageServicePropertyChange: IOMessage = SYS_IOKIT | SUB_IOKIT_COMMON | 0x130;
+
+pub const kIOMessageCopyClientID: IOMessage = SYS_IOKIT | SUB_IOKIT_COMMON | 0x330;
+
+pub const kIOMessageSystemCapabilityChange: IOMessage = SYS_IOKIT | SUB_IOKIT_COMMON | 0x340;
+pub const kIOMessageDeviceSignaledWakeup: IOMessage = SYS_IOKIT | SUB_IOKIT_COMMON | 0x350;
+
+// device power notifications
+pub const kIOMessageCanDevicePowerOff: IOMessage = SYS_IOKIT | SUB_IOKIT_COMMON | 0x200;
+pub const kIOMessageDeviceWillPowerOff: IOMessage = SYS_IOKIT | SUB_IOKIT_COMMON | 0x210;
+pub const kIOMessageDeviceWillNotPowerOff: IOMessage = SYS_IOKIT | SUB_IOKIT_COMMON | 0x220;
+pub const kIOMessageDeviceHasPoweredOn: IOMessage = SYS_IOKIT | SUB_IOKIT_COMMON | 0x230;
+
+// system power notifications
+pub const kIOMessageCanSystemPowerOff: IOMessage = SYS_IOKIT | SUB_IOKIT_COMMON | 0x240;
+pub const kIOMessageSystemWillPowerOff: IOMessage = SYS_IOKIT | SUB_IOKIT_COMMON | 0x250;
+pub const kIOMessageSystemWillNotPowerOff: IOMessage = SYS_IOKIT | SUB_IOKIT_COMMON | 0x260;
+pub const kIOMessageCanSystemSleep: IOMessage = SYS_IOKIT | SUB_IOKIT_COMMON | 0x270;
+pub const kIOMessageSystemWillSleep: IOMessage = SYS_IOKIT | SUB_IOKIT_COMMON | 0x280;
+pub const kIOMessageSystemWillNotSleep: IOMessage = SYS_IOKIT | SUB_IOKIT_COMMON | 0x290;
+pub const kIOMessageSystemHasPoweredOn: IOMessage = SYS_IOKIT | SUB_IOKIT_COMMON | 0x300;
+pub const kIOMessageSystemWillRestart: IOMessage = SYS_IOKIT | SUB_IOKIT_COMMON | 0x310;
+pub const kIOMessageSystemWillPowerOn: IOMessage = SYS_IOKIT | SUB_IOKIT_COMMON | 0x320;
+pub const kIOMessageSystemPagingOff: IOMessage = SYS_IOKIT | SUB_IOKIT_COMMON | 0x255;
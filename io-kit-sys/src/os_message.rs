@@ -0,0 +1,32 @@
+// exports from <IOKit/OSMessageNotification.h>
+
+pub const kFirstIOKitNotificationType: u32 = 100;
+pub const kIOServicePublishNotificationType: u32 = 100;
+pub const kIOServiceMatchedNotificationType: u32 = 101;
+pub const kIOServiceTerminatedNotificationType: u32 = 102;
+pub const kIOAsyncCompletionNotificationType: u32 = 150;
+pub const kIOServiceMessageNotificationType: u32 = 160;
+pub const kLastIOKitNotificationType: u32 = 199;
+
+// reserved bits in a notification type
+pub const kIOKitNoticationTypeMask: u32 = 0x00000fff;
+pub const kIOKitNoticationTypeSizeAdjShift: u32 = 30;
+pub const kIOKitNoticationMsgSizeMask: u32 = 3;
+
+pub const kOSNotificationMessageID: u32 = 53;
+pub const kOSAsyncCompleteMessageID: u32 = 57;
+
+pub const kMaxAsyncArgs: u32 = 16;
+
+// layout of the reference array passed to asynchronous calls
+pub const kOSAsyncRefCount: u32 = 8;
+pub const kOSAsyncRef64Count: u32 = 8;
+
+pub const kIOAsyncReservedIndex: usize = 0;
+pub const kIOAsyncReservedCount: usize = 1;
+pub const kIOAsyncCalloutFuncIndex: usize = kIOAsyncReservedCount;
+pub const kIOAsyncCalloutRefconIndex: usize = kIOAsyncReservedCount + 1;
+pub const kIOAsyncCalloutCount: usize = kIOAsyncReservedCount + 2;
+
+pub type OSAsyncReference = [u32; kOSAsyncRefCount as usize];
+pub type OSAsyncReference64 = [u64; kOSAsyncRef64Count as usize];
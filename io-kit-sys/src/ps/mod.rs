@@ -1,2 +1,3 @@
 pub mod keys;
+#[cfg(target_os = "macos")]
 pub mod power_sources;
@@ -2,11 +2,38 @@
 
 use std::os::raw::c_int;
 
+#[cfg(target_os = "macos")]
 use mach::kern_return::{kern_return_t, KERN_SUCCESS};
 
+// as in <mach/kern_return.h>, for targets the `mach` crate does not build on
+#[cfg(not(target_os = "macos"))]
+type kern_return_t = c_int;
+#[cfg(not(target_os = "macos"))]
+const KERN_SUCCESS: kern_return_t = 0;
+
 // sys_iokit
-const SYS_IOKIT: c_int = ((0x38) & 0x3f) << 26;
-const SUB_IOKIT_COMMON: c_int = ((0) & 0xfff) << 14;
+pub const SYS_IOKIT: c_int = ((0x38) & 0x3f) << 26;
+
+// IOKit subsystems
+pub const SUB_IOKIT_COMMON: c_int = ((0) & 0xfff) << 14;
+pub const SUB_IOKIT_USB: c_int = ((1) & 0xfff) << 14;
+pub const SUB_IOKIT_FIREWIRE: c_int = ((2) & 0xfff) << 14;
+pub const SUB_IOKIT_BLOCK_STORAGE: c_int = ((4) & 0xfff) << 14;
+pub const SUB_IOKIT_GRAPHICS: c_int = ((5) & 0xfff) << 14;
+pub const SUB_IOKIT_NETWORKING: c_int = ((6) & 0xfff) << 14;
+pub const SUB_IOKIT_BLUETOOTH: c_int = ((8) & 0xfff) << 14;
+pub const SUB_IOKIT_PMU: c_int = ((9) & 0xfff) << 14;
+pub const SUB_IOKIT_ACPI: c_int = ((10) & 0xfff) << 14;
+pub const SUB_IOKIT_SMBUS: c_int = ((11) & 0xfff) << 14;
+pub const SUB_IOKIT_AHCI: c_int = ((12) & 0xfff) << 14;
+pub const SUB_IOKIT_POWERMANAGEMENT: c_int = ((13) & 0xfff) << 14;
+pub const SUB_IOKIT_HIDSYSTEM: c_int = ((14) & 0xfff) << 14;
+pub const SUB_IOKIT_SCSI: c_int = ((16) & 0xfff) << 14;
+pub const SUB_IOKIT_USBAUDIO: c_int = ((17) & 0xfff) << 14;
+pub const SUB_IOKIT_PCCARD: c_int = ((21) & 0xfff) << 14;
+pub const SUB_IOKIT_THUNDERBOLT: c_int = ((29) & 0xfff) << 14;
+pub const SUB_IOKIT_VENDOR_SPECIFIC: c_int = ((-2) & 0xfff) << 14;
+pub const SUB_IOKIT_RESERVED: c_int = 0xfff << 14;
 
 // IOReturn
 pub type IOReturn = kern_return_t;
@@ -120,3 +147,109 @@ pub const kIOReturnIsoTooNew: IOReturn = SYS_IOKIT | SUB_IOKIT_COMMON | 0x2ef;
 pub const kIOReturnNotFound: IOReturn = SYS_IOKIT | SUB_IOKIT_COMMON | 0x2f0;
 // should never be seen
 pub const kIOReturnInvalid: IOReturn = SYS_IOKIT | SUB_IOKIT_COMMON | 0x1;
+
+// exports from <IOKit/usb/USB.h>
+
+// USB controller errors
+pub const kIOUSBCRCErr: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x01;
+pub const kIOUSBBitstufErr: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x02;
+pub const kIOUSBDataToggleErr: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x03;
+pub const kIOUSBPIDCheckErr: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x06;
+pub const kIOUSBWrongPIDErr: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x07;
+pub const kIOUSBReserved1Err: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x0a;
+pub const kIOUSBReserved2Err: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x0b;
+pub const kIOUSBBufferOverrunErr: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x0c;
+pub const kIOUSBBufferUnderrunErr: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x0d;
+pub const kIOUSBNotSent1Err: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x0e;
+pub const kIOUSBNotSent2Err: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x0f;
+pub const kIOUSBLinkErr: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x10;
+// USB family errors
+pub const kIOUSBClearPipeStallNotRecursive: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x48;
+pub const kIOUSBDeviceNotHighSpeed: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x49;
+pub const kIOUSBSyncRequestOnWLThread: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x4a;
+pub const kIOUSBHighSpeedSplitError: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x4b;
+pub const kIOUSBLowLatencyFrameListNotPreviouslyAllocated: IOReturn =
+    SYS_IOKIT | SUB_IOKIT_USB | 0x4c;
+pub const kIOUSBLowLatencyBufferNotPreviouslyAllocated: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x4d;
+pub const kIOUSBInterfaceNotFound: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x4e;
+pub const kIOUSBPipeStalled: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x4f;
+pub const kIOUSBTransactionReturned: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x50;
+pub const kIOUSBTransactionTimeout: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x51;
+pub const kIOUSBPortWasSuspended: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x52;
+pub const kIOUSBConfigNotFound: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x56;
+pub const kIOUSBEndpointNotFound: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x57;
+pub const kIOUSBNotEnoughPowerErr: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x5d;
+pub const kIOUSBNotEnoughPipesErr: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x5e;
+pub const kIOUSBNoAsyncPortErr: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x5f;
+pub const kIOUSBTooManyPipesErr: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x60;
+pub const kIOUSBUnknownPipeErr: IOReturn = SYS_IOKIT | SUB_IOKIT_USB | 0x61;
+
+// exports from <IOKit/firewire/IOFireWireFamilyCommon.h>
+
+pub const kIOFireWirePending: IOReturn = SYS_IOKIT | SUB_IOKIT_FIREWIRE;
+pub const kIOFireWireLastDCLToken: IOReturn = SYS_IOKIT | SUB_IOKIT_FIREWIRE | 0x1;
+pub const kIOFireWireConfigROMInvalid: IOReturn = SYS_IOKIT | SUB_IOKIT_FIREWIRE | 0x2;
+pub const kIOFireWireAlreadyRegistered: IOReturn = SYS_IOKIT | SUB_IOKIT_FIREWIRE | 0x3;
+pub const kIOFireWireMultipleTalkers: IOReturn = SYS_IOKIT | SUB_IOKIT_FIREWIRE | 0x4;
+pub const kIOFireWireChannelActive: IOReturn = SYS_IOKIT | SUB_IOKIT_FIREWIRE | 0x5;
+pub const kIOFireWireNoListenerOrTalker: IOReturn = SYS_IOKIT | SUB_IOKIT_FIREWIRE | 0x6;
+pub const kIOFireWireNoChannels: IOReturn = SYS_IOKIT | SUB_IOKIT_FIREWIRE | 0x7;
+pub const kIOFireWireChannelNotAvailable: IOReturn = SYS_IOKIT | SUB_IOKIT_FIREWIRE | 0x8;
+pub const kIOFireWireSeparateBus: IOReturn = SYS_IOKIT | SUB_IOKIT_FIREWIRE | 0x9;
+pub const kIOFireWireBadSelfIDs: IOReturn = SYS_IOKIT | SUB_IOKIT_FIREWIRE | 0xa;
+pub const kIOFireWireLowCableVoltage: IOReturn = SYS_IOKIT | SUB_IOKIT_FIREWIRE | 0xb;
+pub const kIOFireWireInsufficientPower: IOReturn = SYS_IOKIT | SUB_IOKIT_FIREWIRE | 0xc;
+pub const kIOFireWireOutOfTLabels: IOReturn = SYS_IOKIT | SUB_IOKIT_FIREWIRE | 0xd;
+
+// exports from <IOBluetooth/Bluetooth.h>
+
+// device reset interrupted pending operation
+pub const kIOBluetoothDeviceResetError: IOReturn = SYS_IOKIT | SUB_IOKIT_BLUETOOTH | 0x1;
+// attempting to open a connection that already exists
+pub const kIOBluetoothConnectionAlreadyExists: IOReturn = SYS_IOKIT | SUB_IOKIT_BLUETOOTH | 0x2;
+// no HCI controller is present
+pub const kIOBluetoothNoHCIController: IOReturn = SYS_IOKIT | SUB_IOKIT_BLUETOOTH | 0x3;
+// HCI controller does not support changing power states
+pub const kIOBluetoothHCIPowerStatesNotSupported: IOReturn = SYS_IOKIT | SUB_IOKIT_BLUETOOTH | 0x4;
+
+// exports from <USBDriverKit/AppleUSBDefinitions.h>
+
+// DriverKit USB errors reuse the values of the USB family
+pub const kUSBHostReturnPipeStalled: IOReturn = kIOUSBPipeStalled;
+pub const kUSBHostReturnNoPower: IOReturn = kIOUSBNotEnoughPowerErr;
+
+// <IOKit/pccard> and <IOKit/pwr_mgt/IOPM.h> define no codes in their
+// subsystems; the IOPM.h return codes, in `pwr_mgt::pm`, are small plain
+// values that overlap the Mach codes
+
+// exports from <IOKit/hid/IOHIDDescriptorParser.h>
+
+// report descriptor parser errors, returned as plain OSStatus values
+pub const kHIDBaseError: IOReturn = -13950;
+pub const kHIDNullStateErr: IOReturn = -13949;
+pub const kHIDBufferTooSmallErr: IOReturn = -13948;
+pub const kHIDValueOutOfRangeErr: IOReturn = -13947;
+pub const kHIDUsageNotFoundErr: IOReturn = -13946;
+pub const kHIDNotValueArrayErr: IOReturn = -13945;
+pub const kHIDInvalidPreparsedDataErr: IOReturn = -13944;
+pub const kHIDIncompatibleReportErr: IOReturn = -13943;
+pub const kHIDBadLogPhysValuesErr: IOReturn = -13942;
+pub const kHIDInvalidReportTypeErr: IOReturn = -13941;
+pub const kHIDInvalidReportLengthErr: IOReturn = -13940;
+pub const kHIDNullPointerErr: IOReturn = -13939;
+pub const kHIDBadParameterErr: IOReturn = -13938;
+pub const kHIDNotEnoughMemoryErr: IOReturn = -13937;
+pub const kHIDEndOfDescriptorErr: IOReturn = -13936;
+pub const kHIDUsagePageZeroErr: IOReturn = -13935;
+pub const kHIDBadLogicalMinimumErr: IOReturn = -13934;
+pub const kHIDBadLogicalMaximumErr: IOReturn = -13933;
+pub const kHIDInvertedLogicalRangeErr: IOReturn = -13932;
+pub const kHIDInvertedPhysicalRangeErr: IOReturn = -13931;
+pub const kHIDUnmatchedUsageRangeErr: IOReturn = -13930;
+pub const kHIDInvertedUsageRangeErr: IOReturn = -13929;
+pub const kHIDUnmatchedStringRangeErr: IOReturn = -13928;
+pub const kHIDUnmatchedDesignatorRangeErr: IOReturn = -13927;
+pub const kHIDReportSizeZeroErr: IOReturn = -13926;
+pub const kHIDReportCountZeroErr: IOReturn = -13925;
+pub const kHIDReportIDZeroErr: IOReturn = -13924;
+pub const kHIDInvalidRangePageErr: IOReturn = -13923;
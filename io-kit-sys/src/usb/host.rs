@@ -0,0 +1,12 @@
+// exports from <IOKit/usb/IOUSBHostFamilyDefinitions.h>
+
+pub const kIOUSBHostDeviceClassName: *const ::std::os::raw::c_char =
+    b"IOUSBHostDevice\x00" as *const [u8; 16usize] as *const ::std::os::raw::c_char;
+pub const kIOUSBHostInterfaceClassName: *const ::std::os::raw::c_char =
+    b"IOUSBHostInterface\x00" as *const [u8; 19usize] as *const ::std::os::raw::c_char;
+pub const kUSBDevicePropertyLocationID: *const ::std::os::raw::c_char =
+    b"locationID\x00" as *const [u8; 11usize] as *const ::std::os::raw::c_char;
+pub const kUSBDevicePropertySpeed: *const ::std::os::raw::c_char =
+    b"Device Speed\x00" as *const [u8; 13usize] as *const ::std::os::raw::c_char;
+pub const kUSBDevicePropertyAddress: *const ::std::os::raw::c_char =
+    b"USB Address\x00" as *const [u8; 12usize] as *const ::std::os::raw::c_char;
@@ -1,2 +1,3 @@
+pub mod host;
 pub mod lib;
 pub mod usb_spec;
@@ -1,16 +1,63 @@
-use std::ffi::CStr;
+use std::collections::{HashSet, VecDeque};
+use std::ffi::{CStr, CString};
+use std::hash::{Hash, Hasher};
 use std::mem;
 
 use std::os::raw::c_char;
+use std::ptr;
+use std::time::Duration;
 
-use core_foundation::base::TCFType;
+use core_foundation::base::{kCFAllocatorDefault, CFRetain, CFType, TCFType};
 use core_foundation::dictionary::CFDictionary;
 use core_foundation::string::CFString;
-use io_kit_sys::types::{io_iterator_t, io_object_t, io_service_t};
+use io_kit_sys::ret::kIOReturnBadArgument;
+use io_kit_sys::types::{
+    io_iterator_t, io_object_t, io_registry_entry_t, io_service_t, IOOptionBits,
+};
 use io_kit_sys::*;
+use mach::clock_types::mach_timespec_t;
 use mach::kern_return::KERN_SUCCESS;
 
-pub struct IOObject(io_object_t);
+use crate::class::IOClass;
+use crate::error::{Error, WaitError};
+use crate::property::TIOProperties;
+
+pub use crate::plane::IORegistryPlane;
+
+/// Implements `Clone` by retaining the object, `PartialEq` with
+/// `IOObjectIsEqualTo` and `Hash` on the registry entry ID.
+macro_rules! impl_object_identity {
+    ($ty:ident) => {
+        impl Clone for $ty {
+            /// Retains the object.
+            ///
+            /// # Panics
+            ///
+            /// Panics if `IOObjectRetain` fails, which only happens when the
+            /// port name is no longer valid in this task.
+            fn clone(&self) -> $ty {
+                self.retain().expect("failed to retain an IOKit object");
+                $ty(self.0)
+            }
+        }
+
+        impl PartialEq for $ty {
+            fn eq(&self, other: &$ty) -> bool {
+                self.is_equal_to(other)
+            }
+        }
+
+        impl Eq for $ty {}
+
+        impl Hash for $ty {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                registry_entry_id(self.0).hash(state)
+            }
+        }
+    };
+}
+
+pub struct IOObject(pub(crate) io_object_t);
 
 impl Drop for IOObject {
     fn drop(&mut self) {
@@ -18,6 +65,8 @@ impl Drop for IOObject {
     }
 }
 
+impl_object_identity!(IOObject);
+
 impl TIOObject<io_object_t> for IOObject {
     #[inline]
     fn as_concrete_io_object_t(&self) -> io_object_t {
@@ -30,7 +79,8 @@ impl TIOObject<io_object_t> for IOObject {
     }
 }
 
-pub struct IOIterator(io_iterator_t);
+// not `Clone`, as a retained copy would share the position
+pub struct IOIterator(pub(crate) io_iterator_t);
 
 impl Drop for IOIterator {
     fn drop(&mut self) {
@@ -76,7 +126,7 @@ impl TIOObject<io_iterator_t> for IOIterator {
     }
 }
 
-pub struct IOService(io_service_t);
+pub struct IOService(pub(crate) io_service_t);
 
 impl Drop for IOService {
     fn drop(&mut self) {
@@ -84,9 +134,53 @@ impl Drop for IOService {
     }
 }
 
+impl_object_identity!(IOService);
+
 impl IOService {
-    pub fn get_matching_service(matching: CFDictionary) -> Option<IOService> {
+    /// Returns the number of operations, such as matching, in progress on
+    /// the service and its descendants.
+    pub fn get_busy_state(&self) -> Result<u32, Error> {
+        unsafe {
+            let mut busy_state = 0;
+
+            let result = IOServiceGetBusyState(self.0, &mut busy_state);
+
+            if result == KERN_SUCCESS {
+                Ok(busy_state)
+            } else {
+                Err(Error::new(result))
+            }
+        }
+    }
+
+    /// Returns the service as `T` if it is of `T::CLASS_NAME` or a subclass,
+    /// or gives it back otherwise.
+    pub fn downcast<T: IOClass>(self) -> Result<T, IOService> {
+        if self.conforms_to(T::CLASS_NAME) {
+            Ok(unsafe { T::from_service_unchecked(self) })
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Waits until the service is no longer busy, or forever with no timeout.
+    /// Fails with `WaitError::Timeout` once `timeout` passes, and with
+    /// `kIOReturnBadArgument` for timeouts longer than `u32::MAX` seconds,
+    /// which `mach_timespec_t` cannot hold.
+    pub fn wait_quiet(&self, timeout: Option<Duration>) -> Result<(), WaitError> {
+        wait_quiet(timeout, |wait_time| unsafe {
+            IOServiceWaitQuiet(self.0, wait_time)
+        })
+    }
+
+    /// Accepts a raw `CFDictionary` or a `Matching`.
+    pub fn get_matching_service<M: Into<CFDictionary>>(matching: M) -> Option<IOService> {
+        let matching = matching.into();
+
         unsafe {
+            // the call consumes one reference to the dictionary
+            CFRetain(matching.as_CFTypeRef());
+
             let result =
                 IOServiceGetMatchingService(kIOMasterPortDefault, matching.as_CFTypeRef() as _);
 
@@ -98,37 +192,85 @@ impl IOService {
         }
     }
 
-    pub fn get_matching_services(matching: CFDictionary) -> Result<Vec<Self>, i32> {
+    /// Accepts a raw `CFDictionary` or a `Matching`. Services are looked up
+    /// lazily as the iterator advances.
+    pub fn get_matching_services<M: Into<CFDictionary>>(
+        matching: M,
+    ) -> Result<MatchingServices, Error> {
+        let matching = matching.into();
+
         unsafe {
-            let mut io_iterator_t: io_iterator_t = mem::uninitialized();
+            let mut iterator: io_iterator_t = 0;
+
+            // the call consumes one reference to the dictionary
+            CFRetain(matching.as_CFTypeRef());
 
             let result = IOServiceGetMatchingServices(
                 kIOMasterPortDefault,
                 matching.as_CFTypeRef() as _,
-                &mut io_iterator_t,
+                &mut iterator,
             );
 
-            if result != KERN_SUCCESS {
-                return Err(result);
+            if result == KERN_SUCCESS {
+                Ok(MatchingServices {
+                    iterator: IOIterator(iterator),
+                    seen: HashSet::new(),
+                })
+            } else {
+                Err(Error::new(result))
             }
+        }
+    }
+}
+
+/// The services returned by `IOService::get_matching_services`.
+///
+/// When the registry changes during iteration, IOKit invalidates the
+/// iterator; it is then reset and services already yielded are skipped.
+pub struct MatchingServices {
+    iterator: IOIterator,
+    // registry entry IDs of the services yielded so far
+    seen: HashSet<u64>,
+}
 
-            let mut v: Vec<Self> = Vec::new();
+impl Iterator for MatchingServices {
+    type Item = IOService;
 
-            loop {
-                let result = IOIteratorNext(io_iterator_t);
+    fn next(&mut self) -> Option<IOService> {
+        loop {
+            if !self.iterator.is_valid() {
+                self.iterator.reset();
+            }
+
+            let result = unsafe { IOIteratorNext(self.iterator.0) };
 
-                if result == 0 {
-                    break;
+            if result == 0 {
+                if self.iterator.is_valid() {
+                    return None;
                 }
 
-                v.push(IOService(result))
+                continue;
             }
 
-            Ok(v)
+            let service = IOService(result);
+
+            if self.seen.insert(registry_entry_id(result)) {
+                return Some(service);
+            }
         }
     }
 }
 
+impl TIOProperties for IOService {
+    fn copy_cf_property(&self, key: &CFString) -> Option<CFType> {
+        copy_entry_property(self.0, key)
+    }
+
+    fn set_cf_property(&self, key: &CFString, value: &CFType) -> Result<(), Error> {
+        set_entry_property(self.0, key, value)
+    }
+}
+
 impl TIOObject<io_service_t> for IOService {
     #[inline]
     fn as_concrete_io_object_t(&self) -> io_service_t {
@@ -141,6 +283,562 @@ impl TIOObject<io_service_t> for IOService {
     }
 }
 
+pub struct IORegistryEntry(io_registry_entry_t);
+
+impl Drop for IORegistryEntry {
+    fn drop(&mut self) {
+        self.release().unwrap();
+    }
+}
+
+impl_object_identity!(IORegistryEntry);
+
+impl From<IOObject> for IORegistryEntry {
+    fn from(object: IOObject) -> IORegistryEntry {
+        let entry = IORegistryEntry(object.0);
+        mem::forget(object);
+        entry
+    }
+}
+
+impl From<IOService> for IORegistryEntry {
+    fn from(service: IOService) -> IORegistryEntry {
+        let entry = IORegistryEntry(service.0);
+        mem::forget(service);
+        entry
+    }
+}
+
+impl IORegistryEntry {
+    pub fn get_root_entry() -> Option<IORegistryEntry> {
+        unsafe {
+            let result = IORegistryGetRootEntry(kIOMasterPortDefault);
+
+            if result != 0 {
+                Some(IORegistryEntry(result))
+            } else {
+                None
+            }
+        }
+    }
+
+    pub fn from_path(path: &str) -> Option<IORegistryEntry> {
+        let path = CString::new(path).ok()?;
+
+        unsafe {
+            let result = IORegistryEntryFromPath(kIOMasterPortDefault, path.as_ptr() as *mut _);
+
+            if result != 0 {
+                Some(IORegistryEntry(result))
+            } else {
+                None
+            }
+        }
+    }
+
+    pub fn get_name(&self) -> Result<String, Error> {
+        unsafe {
+            let mut buf = [0 as c_char; IO_NAME_LEN];
+
+            let result = IORegistryEntryGetName(self.0, buf.as_mut_ptr());
+
+            if result == KERN_SUCCESS {
+                Ok(string_from_buf(&buf))
+            } else {
+                Err(Error::new(result))
+            }
+        }
+    }
+
+    pub fn get_name_in_plane(&self, plane: IORegistryPlane) -> Result<String, Error> {
+        unsafe {
+            let mut buf = [0 as c_char; IO_NAME_LEN];
+
+            let result = IORegistryEntryGetNameInPlane(self.0, plane.as_ptr(), buf.as_mut_ptr());
+
+            if result == KERN_SUCCESS {
+                Ok(string_from_buf(&buf))
+            } else {
+                Err(Error::new(result))
+            }
+        }
+    }
+
+    pub fn get_location_in_plane(&self, plane: IORegistryPlane) -> Result<String, Error> {
+        unsafe {
+            let mut buf = [0 as c_char; IO_NAME_LEN];
+
+            let result =
+                IORegistryEntryGetLocationInPlane(self.0, plane.as_ptr(), buf.as_mut_ptr());
+
+            if result == KERN_SUCCESS {
+                Ok(string_from_buf(&buf))
+            } else {
+                Err(Error::new(result))
+            }
+        }
+    }
+
+    pub fn get_path(&self, plane: IORegistryPlane) -> Result<String, Error> {
+        unsafe {
+            let mut buf = [0 as c_char; IO_STRING_LEN];
+
+            let result = IORegistryEntryGetPath(self.0, plane.as_ptr(), buf.as_mut_ptr());
+
+            if result == KERN_SUCCESS {
+                Ok(string_from_buf(&buf))
+            } else {
+                Err(Error::new(result))
+            }
+        }
+    }
+
+    /// Like `get_path`, but not limited to the 512 bytes of an `io_string_t`.
+    pub fn copy_path(&self, plane: IORegistryPlane) -> Option<String> {
+        unsafe {
+            let result = IORegistryEntryCopyPath(self.0, plane.as_ptr());
+
+            if result.is_null() {
+                None
+            } else {
+                Some(CFString::wrap_under_create_rule(result).to_string())
+            }
+        }
+    }
+
+    pub fn get_registry_entry_id(&self) -> Result<u64, Error> {
+        unsafe {
+            let mut entry_id = 0;
+
+            let result = IORegistryEntryGetRegistryEntryID(self.0, &mut entry_id);
+
+            if result == KERN_SUCCESS {
+                Ok(entry_id)
+            } else {
+                Err(Error::new(result))
+            }
+        }
+    }
+
+    pub fn create_cf_properties(&self) -> Result<CFDictionary<CFString, CFType>, Error> {
+        unsafe {
+            let mut properties = std::ptr::null_mut();
+
+            let result =
+                IORegistryEntryCreateCFProperties(self.0, &mut properties, kCFAllocatorDefault, 0);
+
+            if result != KERN_SUCCESS {
+                return Err(Error::new(result));
+            }
+
+            if properties.is_null() {
+                Ok(CFDictionary::from_CFType_pairs(&[]))
+            } else {
+                Ok(CFDictionary::wrap_under_create_rule(properties as *const _))
+            }
+        }
+    }
+
+    pub fn create_cf_property(&self, key: &str) -> Option<CFType> {
+        let key = CFString::new(key);
+
+        unsafe {
+            let result = IORegistryEntryCreateCFProperty(
+                self.0,
+                key.as_concrete_TypeRef(),
+                kCFAllocatorDefault,
+                0,
+            );
+
+            if result.is_null() {
+                None
+            } else {
+                Some(TCFType::wrap_under_create_rule(result))
+            }
+        }
+    }
+
+    /// Looks `key` up on this entry and, depending on `options`
+    /// (`kIORegistryIterateRecursively`, `kIORegistryIterateParents`), on its
+    /// children or parents in `plane`.
+    pub fn search_cf_property(
+        &self,
+        plane: IORegistryPlane,
+        key: &str,
+        options: IOOptionBits,
+    ) -> Option<CFType> {
+        let key = CFString::new(key);
+
+        unsafe {
+            let result = IORegistryEntrySearchCFProperty(
+                self.0,
+                plane.as_ptr(),
+                key.as_concrete_TypeRef(),
+                kCFAllocatorDefault,
+                options,
+            );
+
+            if result.is_null() {
+                None
+            } else {
+                Some(TCFType::wrap_under_create_rule(result))
+            }
+        }
+    }
+
+    pub fn get_child_iterator(&self, plane: IORegistryPlane) -> Result<IOIterator, Error> {
+        unsafe {
+            let mut iterator: io_iterator_t = 0;
+
+            let result = IORegistryEntryGetChildIterator(self.0, plane.as_ptr(), &mut iterator);
+
+            if result == KERN_SUCCESS {
+                Ok(IOIterator(iterator))
+            } else {
+                Err(Error::new(result))
+            }
+        }
+    }
+
+    pub fn get_child_entry(&self, plane: IORegistryPlane) -> Result<IORegistryEntry, Error> {
+        unsafe {
+            let mut child: io_registry_entry_t = 0;
+
+            let result = IORegistryEntryGetChildEntry(self.0, plane.as_ptr(), &mut child);
+
+            if result == KERN_SUCCESS {
+                Ok(IORegistryEntry(child))
+            } else {
+                Err(Error::new(result))
+            }
+        }
+    }
+
+    pub fn get_parent_iterator(&self, plane: IORegistryPlane) -> Result<IOIterator, Error> {
+        unsafe {
+            let mut iterator: io_iterator_t = 0;
+
+            let result = IORegistryEntryGetParentIterator(self.0, plane.as_ptr(), &mut iterator);
+
+            if result == KERN_SUCCESS {
+                Ok(IOIterator(iterator))
+            } else {
+                Err(Error::new(result))
+            }
+        }
+    }
+
+    pub fn get_parent_entry(&self, plane: IORegistryPlane) -> Result<IORegistryEntry, Error> {
+        unsafe {
+            let mut parent: io_registry_entry_t = 0;
+
+            let result = IORegistryEntryGetParentEntry(self.0, plane.as_ptr(), &mut parent);
+
+            if result == KERN_SUCCESS {
+                Ok(IORegistryEntry(parent))
+            } else {
+                Err(Error::new(result))
+            }
+        }
+    }
+
+    pub fn in_plane(&self, plane: IORegistryPlane) -> bool {
+        unsafe { IORegistryEntryInPlane(self.0, plane.as_ptr()) != 0 }
+    }
+
+    /// Walks the children of this entry in `plane`, or its parents if `options`
+    /// contains `kIORegistryIterateParents`. See `IORegistryWalker`.
+    pub fn walk(
+        &self,
+        plane: IORegistryPlane,
+        options: IOOptionBits,
+        traversal: IORegistryTraversal,
+    ) -> Result<IORegistryWalker, Error> {
+        IORegistryWalker::create(Some(self), plane, options, traversal)
+    }
+
+    fn create_iterator(
+        &self,
+        plane: IORegistryPlane,
+        options: IOOptionBits,
+    ) -> Result<IOIterator, Error> {
+        unsafe {
+            let mut iterator: io_iterator_t = 0;
+
+            let result =
+                IORegistryEntryCreateIterator(self.0, plane.as_ptr(), options, &mut iterator);
+
+            if result == KERN_SUCCESS {
+                Ok(IOIterator(iterator))
+            } else {
+                Err(Error::new(result))
+            }
+        }
+    }
+}
+
+impl TIOProperties for IORegistryEntry {
+    fn copy_cf_property(&self, key: &CFString) -> Option<CFType> {
+        copy_entry_property(self.0, key)
+    }
+
+    fn set_cf_property(&self, key: &CFString, value: &CFType) -> Result<(), Error> {
+        set_entry_property(self.0, key, value)
+    }
+}
+
+impl TIOObject<io_registry_entry_t> for IORegistryEntry {
+    #[inline]
+    fn as_concrete_io_object_t(&self) -> io_registry_entry_t {
+        self.0
+    }
+
+    #[inline]
+    fn as_io_object_t(&self) -> io_object_t {
+        self.as_concrete_io_object_t()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IORegistryTraversal {
+    DepthFirst,
+    BreadthFirst,
+}
+
+enum Walk {
+    DepthFirst {
+        iterator: IOIterator,
+        depth: usize,
+        enter: bool,
+    },
+    BreadthFirst {
+        current: Option<(usize, IOIterator)>,
+        queue: VecDeque<(usize, IORegistryEntry)>,
+        expand: Option<(usize, IORegistryEntry)>,
+        visited: HashSet<u64>,
+    },
+}
+
+/// Walks a plane of the I/O Registry, yielding `(depth, entry)` pairs.
+///
+/// Depth is relative to where the walk started: the direct children (or
+/// parents, with `kIORegistryIterateParents`) of the starting entry are at
+/// depth 1. Without `kIORegistryIterateRecursively` only that first level is
+/// visited.
+pub struct IORegistryWalker {
+    plane: IORegistryPlane,
+    options: IOOptionBits,
+    walk: Walk,
+}
+
+impl IORegistryWalker {
+    /// Walks the registry starting at its root entry.
+    pub fn new(
+        plane: IORegistryPlane,
+        options: IOOptionBits,
+        traversal: IORegistryTraversal,
+    ) -> Result<IORegistryWalker, Error> {
+        IORegistryWalker::create(None, plane, options, traversal)
+    }
+
+    fn create(
+        start: Option<&IORegistryEntry>,
+        plane: IORegistryPlane,
+        options: IOOptionBits,
+        traversal: IORegistryTraversal,
+    ) -> Result<IORegistryWalker, Error> {
+        // recursion is driven from here so that depths can be tracked and
+        // subtrees pruned, the kernel iterator only ever moves one level
+        let iterator_options = options & kIORegistryIterateParents;
+
+        let iterator = match start {
+            Some(entry) => entry.create_iterator(plane, iterator_options)?,
+            None => unsafe {
+                let mut iterator: io_iterator_t = 0;
+
+                let result = IORegistryCreateIterator(
+                    kIOMasterPortDefault,
+                    plane.as_ptr(),
+                    iterator_options,
+                    &mut iterator,
+                );
+
+                if result != KERN_SUCCESS {
+                    return Err(Error::new(result));
+                }
+
+                IOIterator(iterator)
+            },
+        };
+
+        let walk = match traversal {
+            IORegistryTraversal::DepthFirst => Walk::DepthFirst {
+                iterator,
+                depth: 1,
+                enter: false,
+            },
+            IORegistryTraversal::BreadthFirst => Walk::BreadthFirst {
+                current: Some((1, iterator)),
+                queue: VecDeque::new(),
+                expand: None,
+                visited: HashSet::new(),
+            },
+        };
+
+        Ok(IORegistryWalker {
+            plane,
+            options,
+            walk,
+        })
+    }
+
+    /// Skips the descendants of the entry returned last.
+    pub fn prune(&mut self) {
+        match self.walk {
+            Walk::DepthFirst { ref mut enter, .. } => *enter = false,
+            Walk::BreadthFirst { ref mut expand, .. } => *expand = None,
+        }
+    }
+
+    fn recursive(&self) -> bool {
+        self.options & kIORegistryIterateRecursively != 0
+    }
+}
+
+impl Iterator for IORegistryWalker {
+    type Item = (usize, IORegistryEntry);
+
+    fn next(&mut self) -> Option<(usize, IORegistryEntry)> {
+        let recursive = self.recursive();
+        let plane = self.plane;
+        let iterator_options = self.options & kIORegistryIterateParents;
+
+        match self.walk {
+            Walk::DepthFirst {
+                ref iterator,
+                ref mut depth,
+                ref mut enter,
+            } => unsafe {
+                if *enter {
+                    *enter = false;
+
+                    if IORegistryIteratorEnterEntry(iterator.as_io_object_t()) == KERN_SUCCESS {
+                        *depth += 1;
+                    }
+                }
+
+                loop {
+                    let result = IOIteratorNext(iterator.as_io_object_t());
+
+                    if result != 0 {
+                        *enter = recursive;
+                        return Some((*depth, IORegistryEntry(result)));
+                    }
+
+                    if *depth == 1
+                        || IORegistryIteratorExitEntry(iterator.as_io_object_t()) != KERN_SUCCESS
+                    {
+                        return None;
+                    }
+
+                    *depth -= 1;
+                }
+            },
+            Walk::BreadthFirst {
+                ref mut current,
+                ref mut queue,
+                ref mut expand,
+                ref mut visited,
+            } => {
+                if let Some(entry) = expand.take() {
+                    queue.push_back(entry);
+                }
+
+                loop {
+                    if let Some((depth, ref mut iterator)) = *current {
+                        match iterator.next() {
+                            Some(object) => {
+                                let entry = IORegistryEntry::from(object);
+
+                                // an entry with several parents shows up once per parent
+                                if let Ok(entry_id) = entry.get_registry_entry_id() {
+                                    if !visited.insert(entry_id) {
+                                        continue;
+                                    }
+                                }
+
+                                if recursive {
+                                    *expand = Some((depth, entry.clone()));
+                                }
+
+                                return Some((depth, entry));
+                            }
+                            None => *current = None,
+                        }
+                    }
+
+                    let (depth, entry) = queue.pop_front()?;
+
+                    if let Ok(iterator) = entry.create_iterator(plane, iterator_options) {
+                        *current = Some((depth + 1, iterator));
+                    }
+                }
+            }
+        }
+    }
+}
+
+// sizes of `io_name_t` and `io_string_t`
+const IO_NAME_LEN: usize = 128;
+const IO_STRING_LEN: usize = 512;
+
+fn copy_entry_property(entry: io_registry_entry_t, key: &CFString) -> Option<CFType> {
+    unsafe {
+        let result = IORegistryEntryCreateCFProperty(
+            entry,
+            key.as_concrete_TypeRef(),
+            kCFAllocatorDefault,
+            0,
+        );
+
+        if result.is_null() {
+            None
+        } else {
+            Some(TCFType::wrap_under_create_rule(result))
+        }
+    }
+}
+
+fn set_entry_property(
+    entry: io_registry_entry_t,
+    key: &CFString,
+    value: &CFType,
+) -> Result<(), Error> {
+    unsafe {
+        let result =
+            IORegistryEntrySetCFProperty(entry, key.as_concrete_TypeRef(), value.as_CFTypeRef());
+
+        if result == KERN_SUCCESS {
+            Ok(())
+        } else {
+            Err(Error::new(result))
+        }
+    }
+}
+
+// objects that are not registry entries all hash alike
+fn registry_entry_id(object: io_object_t) -> u64 {
+    let mut entry_id = 0;
+
+    unsafe { IORegistryEntryGetRegistryEntryID(object, &mut entry_id) };
+
+    entry_id
+}
+
+fn string_from_buf(buf: &[c_char]) -> String {
+    unsafe { CStr::from_ptr(buf.as_ptr()).to_string_lossy().into_owned() }
+}
+
 pub trait TIOObject<concrete_io_object_t> {
     /// Returns the object as its concrete `io_object_t`.
     fn as_concrete_io_object_t(&self) -> concrete_io_object_t;
@@ -148,31 +846,31 @@ pub trait TIOObject<concrete_io_object_t> {
     /// Returns the object as a raw `io_object_t`.
     fn as_io_object_t(&self) -> io_object_t;
 
-    fn release(&self) -> Result<(), i32> {
+    fn release(&self) -> Result<(), Error> {
         unsafe {
             let result = IOObjectRelease(self.as_io_object_t());
 
             if result == KERN_SUCCESS {
                 Ok(())
             } else {
-                Err(result)
+                Err(Error::new(result))
             }
         }
     }
 
-    fn retain(&self) -> Result<(), i32> {
+    fn retain(&self) -> Result<(), Error> {
         unsafe {
             let result = IOObjectRetain(self.as_io_object_t());
 
             if result == KERN_SUCCESS {
                 Ok(())
             } else {
-                Err(result)
+                Err(Error::new(result))
             }
         }
     }
 
-    fn get_class(&self) -> Result<String, i32> {
+    fn get_class(&self) -> Result<String, Error> {
         unsafe {
             let mut buf = Vec::<c_char>::with_capacity(128);
 
@@ -183,7 +881,7 @@ pub trait TIOObject<concrete_io_object_t> {
                     CStr::from_ptr(buf.as_ptr()).to_str().unwrap().to_string(),
                 ))
             } else {
-                Err(result)
+                Err(Error::new(result))
             }
         }
     }
@@ -224,11 +922,18 @@ pub trait TIOObject<concrete_io_object_t> {
         }
     }
 
-    fn conforms_to(&self, class_name: *mut c_char) -> bool {
-        unsafe { IOObjectConformsTo(self.as_io_object_t(), class_name) != 0 }
+    fn conforms_to(&self, class_name: &str) -> bool {
+        let class_name = match CString::new(class_name) {
+            Ok(class_name) => class_name,
+            Err(_) => return false,
+        };
+
+        unsafe {
+            IOObjectConformsTo(self.as_io_object_t(), class_name.as_ptr() as *mut c_char) != 0
+        }
     }
 
-    fn is_equal_to(&self, object: IOObject) -> bool {
+    fn is_equal_to<C, O: TIOObject<C>>(&self, object: &O) -> bool {
         unsafe { IOObjectIsEqualTo(self.as_io_object_t(), object.as_io_object_t()) != 0 }
     }
 
@@ -245,6 +950,56 @@ pub trait TIOObject<concrete_io_object_t> {
     }
 }
 
+/// Returns the busy state of the whole registry.
+pub fn io_kit_get_busy_state() -> Result<u32, Error> {
+    unsafe {
+        let mut busy_state = 0;
+
+        let result = IOKitGetBusyState(kIOMasterPortDefault, &mut busy_state);
+
+        if result == KERN_SUCCESS {
+            Ok(busy_state)
+        } else {
+            Err(Error::new(result))
+        }
+    }
+}
+
+/// Waits until no service in the registry is busy, as `IOService::wait_quiet`.
+pub fn io_kit_wait_quiet(timeout: Option<Duration>) -> Result<(), WaitError> {
+    wait_quiet(timeout, |wait_time| unsafe {
+        IOKitWaitQuiet(kIOMasterPortDefault, wait_time)
+    })
+}
+
+fn wait_quiet<F>(timeout: Option<Duration>, wait: F) -> Result<(), WaitError>
+where
+    F: FnOnce(*mut mach_timespec_t) -> i32,
+{
+    let mut wait_time = match timeout {
+        Some(timeout) if timeout.as_secs() > u32::MAX as u64 => {
+            return Err(WaitError::Error(Error::new(kIOReturnBadArgument)))
+        }
+        Some(timeout) => Some(mach_timespec_t {
+            tv_sec: timeout.as_secs() as u32,
+            tv_nsec: timeout.subsec_nanos() as i32,
+        }),
+        None => None,
+    };
+
+    let result = wait(
+        wait_time
+            .as_mut()
+            .map_or(ptr::null_mut(), |wait_time| wait_time as *mut _),
+    );
+
+    if result == KERN_SUCCESS {
+        Ok(())
+    } else {
+        Err(Error::new(result).into())
+    }
+}
+
 pub fn io_service_matching(name: *const c_char) -> Option<CFDictionary> {
     unsafe {
         let result = IOServiceMatching(name);
@@ -252,7 +1007,41 @@ pub fn io_service_matching(name: *const c_char) -> Option<CFDictionary> {
         if result.is_null() {
             None
         } else {
-            Some(TCFType::wrap_under_get_rule(result as *const _))
+            Some(TCFType::wrap_under_create_rule(result as *const _))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use io_kit_sys::ret::{kIOReturnBusy, kIOReturnTimeout};
+
+    use super::*;
+
+    #[test]
+    fn wait_quiet_timeouts() {
+        let result = wait_quiet(Some(Duration::new(2, 500)), |wait_time| unsafe {
+            assert_eq!((*wait_time).tv_sec, 2);
+            assert_eq!((*wait_time).tv_nsec, 500);
+            kIOReturnTimeout
+        });
+        assert_eq!(result, Err(WaitError::Timeout));
+
+        let result = wait_quiet(None, |wait_time| {
+            assert!(wait_time.is_null());
+            kIOReturnBusy
+        });
+        assert_eq!(result, Err(WaitError::Error(Error::new(kIOReturnBusy))));
+
+        let result = wait_quiet(Some(Duration::from_secs(u32::MAX as u64)), |_| KERN_SUCCESS);
+        assert_eq!(result, Ok(()));
+
+        let result = wait_quiet(Some(Duration::from_secs(u32::MAX as u64 + 1)), |_| {
+            panic!("the timeout should have been rejected")
+        });
+        assert_eq!(
+            result,
+            Err(WaitError::Error(Error::new(kIOReturnBadArgument)))
+        );
+    }
+}
@@ -0,0 +1,217 @@
+//! Services typed by their IOKit class, obtained with `IOService::downcast`.
+
+use core_foundation::base::CFType;
+use core_foundation::string::CFString;
+
+use crate::base::IOService;
+use crate::error::Error;
+use crate::keys::typed::*;
+use crate::property::TIOProperties;
+
+/// A wrapper for services of one IOKit class and its subclasses.
+pub trait IOClass: Sized {
+    const CLASS_NAME: &'static str;
+
+    /// Wraps `service` without checking its class.
+    ///
+    /// # Safety
+    ///
+    /// `service` must conform to `CLASS_NAME`.
+    unsafe fn from_service_unchecked(service: IOService) -> Self;
+
+    fn as_service(&self) -> &IOService;
+
+    fn into_service(self) -> IOService;
+}
+
+macro_rules! impl_io_class {
+    ($ty:ident, $class_name:expr) => {
+        impl IOClass for $ty {
+            const CLASS_NAME: &'static str = $class_name;
+
+            unsafe fn from_service_unchecked(service: IOService) -> $ty {
+                $ty(service)
+            }
+
+            fn as_service(&self) -> &IOService {
+                &self.0
+            }
+
+            fn into_service(self) -> IOService {
+                self.0
+            }
+        }
+
+        impl TIOProperties for $ty {
+            fn copy_cf_property(&self, key: &CFString) -> Option<CFType> {
+                self.0.copy_cf_property(key)
+            }
+
+            fn set_cf_property(&self, key: &CFString, value: &CFType) -> Result<(), Error> {
+                self.0.set_cf_property(key, value)
+            }
+        }
+    };
+}
+
+/// An `IOHIDDevice` service.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct IOHIDDeviceService(IOService);
+
+impl_io_class!(IOHIDDeviceService, "IOHIDDevice");
+
+impl IOHIDDeviceService {
+    pub fn vendor_id(&self) -> Option<u32> {
+        self.get(&kIOHIDVendorIDKey)
+    }
+
+    pub fn product_id(&self) -> Option<u32> {
+        self.get(&kIOHIDProductIDKey)
+    }
+
+    pub fn version_number(&self) -> Option<u32> {
+        self.get(&kIOHIDVersionNumberKey)
+    }
+
+    pub fn manufacturer(&self) -> Option<String> {
+        self.get(&kIOHIDManufacturerKey)
+    }
+
+    pub fn product(&self) -> Option<String> {
+        self.get(&kIOHIDProductKey)
+    }
+
+    pub fn serial_number(&self) -> Option<String> {
+        self.get(&kIOHIDSerialNumberKey)
+    }
+
+    /// The transport, such as `"USB"` or `"Bluetooth"`.
+    pub fn transport(&self) -> Option<String> {
+        self.get(&kIOHIDTransportKey)
+    }
+
+    pub fn location_id(&self) -> Option<u32> {
+        self.get(&kIOHIDLocationIDKey)
+    }
+
+    pub fn primary_usage_page(&self) -> Option<u32> {
+        self.get(&kIOHIDPrimaryUsagePageKey)
+    }
+
+    pub fn primary_usage(&self) -> Option<u32> {
+        self.get(&kIOHIDPrimaryUsageKey)
+    }
+}
+
+/// An `IOUSBHostDevice` service.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct IOUSBHostDeviceService(IOService);
+
+impl_io_class!(IOUSBHostDeviceService, "IOUSBHostDevice");
+
+impl IOUSBHostDeviceService {
+    pub fn vendor_id(&self) -> Option<u16> {
+        self.get(&kUSBVendorID)
+    }
+
+    pub fn product_id(&self) -> Option<u16> {
+        self.get(&kUSBProductID)
+    }
+
+    /// The `bcdDevice` release number.
+    pub fn device_release(&self) -> Option<u16> {
+        self.get(&kUSBDeviceReleaseNumber)
+    }
+
+    pub fn device_class(&self) -> Option<u8> {
+        self.get(&kUSBDeviceClass)
+    }
+
+    pub fn device_subclass(&self) -> Option<u8> {
+        self.get(&kUSBDeviceSubClass)
+    }
+
+    pub fn device_protocol(&self) -> Option<u8> {
+        self.get(&kUSBDeviceProtocol)
+    }
+
+    pub fn vendor_name(&self) -> Option<String> {
+        self.get(&kUSBVendorString)
+    }
+
+    pub fn product_name(&self) -> Option<String> {
+        self.get(&kUSBProductString)
+    }
+
+    pub fn serial_number(&self) -> Option<String> {
+        self.get(&kUSBSerialNumberString)
+    }
+
+    pub fn location_id(&self) -> Option<u32> {
+        self.get(&kUSBDevicePropertyLocationID)
+    }
+
+    /// The `kUSBDeviceSpeed*` value the device runs at.
+    pub fn speed(&self) -> Option<u32> {
+        self.get(&kUSBDevicePropertySpeed)
+    }
+
+    pub fn address(&self) -> Option<u32> {
+        self.get(&kUSBDevicePropertyAddress)
+    }
+}
+
+/// An `IOMedia` service: a whole disk or a partition of one.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct IOMediaService(IOService);
+
+impl_io_class!(IOMediaService, "IOMedia");
+
+impl IOMediaService {
+    /// The size in bytes.
+    pub fn size(&self) -> Option<u64> {
+        self.get(&kIOMediaSizeKey)
+    }
+
+    pub fn preferred_block_size(&self) -> Option<u64> {
+        self.get(&kIOMediaPreferredBlockSizeKey)
+    }
+
+    /// The partition type or filesystem of the contents.
+    pub fn content(&self) -> Option<String> {
+        self.get(&kIOMediaContentKey)
+    }
+
+    pub fn content_hint(&self) -> Option<String> {
+        self.get(&kIOMediaContentHintKey)
+    }
+
+    pub fn uuid(&self) -> Option<String> {
+        self.get(&kIOMediaUUIDKey)
+    }
+
+    /// The name of the device node, such as `"disk2s1"`.
+    pub fn bsd_name(&self) -> Option<String> {
+        self.get(&kIOBSDNameKey)
+    }
+
+    pub fn is_whole(&self) -> bool {
+        self.get(&kIOMediaWholeKey).unwrap_or(false)
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        self.get(&kIOMediaLeafKey).unwrap_or(false)
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.get(&kIOMediaWritableKey).unwrap_or(false)
+    }
+
+    pub fn is_removable(&self) -> bool {
+        self.get(&kIOMediaRemovableKey).unwrap_or(false)
+    }
+
+    pub fn is_ejectable(&self) -> bool {
+        self.get(&kIOMediaEjectableKey).unwrap_or(false)
+    }
+}
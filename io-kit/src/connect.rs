@@ -0,0 +1,542 @@
+//! Connections to the user clients of drivers.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::os::raw::c_void;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::ptr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::{Context, Poll, Waker};
+
+use io_kit_sys::os_message::{
+    kIOAsyncCalloutFuncIndex, kIOAsyncCalloutRefconIndex, kOSAsyncRef64Count, OSAsyncReference64,
+};
+use io_kit_sys::ret::{kIOReturnAborted, kIOReturnNoMemory, kIOReturnSuccess, IOReturn};
+use io_kit_sys::types::{io_connect_t, io_service_t};
+use io_kit_sys::*;
+use mach::port::mach_port_t;
+use mach::traps::mach_task_self;
+
+use crate::base::{IOService, TIOObject};
+use crate::error::Error;
+use crate::notification::NotificationPort;
+use crate::runloop::{BackgroundRunLoop, RunLoopBound};
+
+type AsyncCallback = Box<dyn FnOnce(AsyncCompletion) + Send>;
+
+// asynchronous calls awaiting completion, by call ID, with the ID of their
+// connection
+static PENDING_CALLS: Mutex<BTreeMap<u64, (u64, AsyncCallback)>> = Mutex::new(BTreeMap::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// The outputs of `IOConnection::call_method`, truncated to what the driver
+/// returned.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MethodOutput {
+    pub scalars: Vec<u64>,
+    pub structure: Vec<u8>,
+}
+
+/// The completion of an asynchronous call.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AsyncCompletion {
+    pub result: Result<(), Error>,
+    pub args: Vec<u64>,
+}
+
+/// An open connection to a user client, closed on drop.
+///
+/// Completions of asynchronous calls are received on a background run loop
+/// thread. Calls still pending when the connection is dropped complete with
+/// `kIOReturnAborted`.
+pub struct IOConnection {
+    connect: io_connect_t,
+    id: u64,
+    // the port and its mach port, created by the first asynchronous call
+    async_port: OnceLock<(RunLoopBound<NotificationPort>, mach_port_t)>,
+}
+
+impl Drop for IOConnection {
+    fn drop(&mut self) {
+        unsafe {
+            IOServiceClose(self.connect);
+        }
+
+        // no completion can be delivered once the port is gone
+        drop(self.async_port.take());
+
+        let aborted = {
+            let mut pending = PENDING_CALLS.lock().unwrap();
+            let ids: Vec<u64> = pending
+                .iter()
+                .filter(|&(_, &(connection, _))| connection == self.id)
+                .map(|(&id, _)| id)
+                .collect();
+
+            ids.into_iter()
+                .filter_map(|id| pending.remove(&id))
+                .collect::<Vec<_>>()
+        };
+
+        for (_, callback) in aborted {
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+                callback(AsyncCompletion {
+                    result: Err(Error::new(kIOReturnAborted)),
+                    args: Vec::new(),
+                })
+            }));
+        }
+    }
+}
+
+impl IOConnection {
+    /// Opens a connection of `connection_type` to `service` for the current task.
+    pub fn open(service: &IOService, connection_type: u32) -> Result<IOConnection, Error> {
+        unsafe {
+            let mut connect: io_connect_t = 0;
+
+            let result = IOServiceOpen(
+                service.as_io_object_t(),
+                mach_task_self(),
+                connection_type,
+                &mut connect,
+            );
+
+            if result == kIOReturnSuccess {
+                Ok(IOConnection {
+                    connect,
+                    id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+                    async_port: OnceLock::new(),
+                })
+            } else {
+                Err(Error::new(result))
+            }
+        }
+    }
+
+    pub fn as_connect_t(&self) -> io_connect_t {
+        self.connect
+    }
+
+    /// Registers `port` to receive the notifications of `notification_type`
+    /// the user client sends, tagged with `reference`.
+    pub fn set_notification_port(
+        &self,
+        notification_type: u32,
+        port: &NotificationPort,
+        reference: usize,
+    ) -> Result<(), Error> {
+        unsafe {
+            let result = IOConnectSetNotificationPort(
+                self.connect,
+                notification_type,
+                port.get_mach_port(),
+                reference,
+            );
+
+            if result == kIOReturnSuccess {
+                Ok(())
+            } else {
+                Err(Error::new(result))
+            }
+        }
+    }
+
+    pub fn get_service(&self) -> Result<IOService, Error> {
+        unsafe {
+            let mut service: io_service_t = 0;
+
+            let result = IOConnectGetService(self.connect, &mut service);
+
+            if result == kIOReturnSuccess {
+                Ok(IOService(service))
+            } else {
+                Err(Error::new(result))
+            }
+        }
+    }
+
+    /// Calls a method taking and returning only scalars. At most
+    /// `output_count` scalars are returned.
+    pub fn call_scalar(
+        &self,
+        selector: u32,
+        input: &[u64],
+        output_count: usize,
+    ) -> Result<Vec<u64>, Error> {
+        let mut output = vec![0u64; output_count];
+        let mut output_cnt = output_count as u32;
+
+        unsafe {
+            let result = IOConnectCallScalarMethod(
+                self.connect,
+                selector,
+                input.as_ptr(),
+                input.len() as u32,
+                output.as_mut_ptr(),
+                &mut output_cnt,
+            );
+
+            if result == kIOReturnSuccess {
+                output.truncate(output_cnt as usize);
+                Ok(output)
+            } else {
+                Err(Error::new(result))
+            }
+        }
+    }
+
+    /// Calls a method taking and returning only a structure. At most
+    /// `output_size` bytes are returned.
+    pub fn call_struct(
+        &self,
+        selector: u32,
+        input: &[u8],
+        output_size: usize,
+    ) -> Result<Vec<u8>, Error> {
+        let mut output = vec![0u8; output_size];
+        let mut output_cnt = output_size;
+
+        unsafe {
+            let result = IOConnectCallStructMethod(
+                self.connect,
+                selector,
+                struct_ptr(input),
+                input.len(),
+                struct_mut_ptr(&mut output),
+                &mut output_cnt,
+            );
+
+            if result == kIOReturnSuccess {
+                output.truncate(output_cnt);
+                Ok(output)
+            } else {
+                Err(Error::new(result))
+            }
+        }
+    }
+
+    /// Calls a method taking and returning both scalars and a structure.
+    pub fn call_method(
+        &self,
+        selector: u32,
+        scalar_input: &[u64],
+        struct_input: &[u8],
+        scalar_output_count: usize,
+        struct_output_size: usize,
+    ) -> Result<MethodOutput, Error> {
+        let mut scalars = vec![0u64; scalar_output_count];
+        let mut scalars_cnt = scalar_output_count as u32;
+        let mut structure = vec![0u8; struct_output_size];
+        let mut structure_cnt = struct_output_size;
+
+        unsafe {
+            let result = IOConnectCallMethod(
+                self.connect,
+                selector,
+                scalar_input.as_ptr(),
+                scalar_input.len() as u32,
+                struct_ptr(struct_input),
+                struct_input.len(),
+                scalars.as_mut_ptr(),
+                &mut scalars_cnt,
+                struct_mut_ptr(&mut structure),
+                &mut structure_cnt,
+            );
+
+            if result == kIOReturnSuccess {
+                scalars.truncate(scalars_cnt as usize);
+                structure.truncate(structure_cnt);
+                Ok(MethodOutput { scalars, structure })
+            } else {
+                Err(Error::new(result))
+            }
+        }
+    }
+
+    /// Starts an asynchronous method taking and returning only scalars.
+    pub fn call_async_scalar(
+        &self,
+        selector: u32,
+        input: &[u64],
+        output_count: usize,
+    ) -> Result<AsyncCall, Error> {
+        let (callback, completion) = AsyncCall::completion();
+        let mut output = vec![0u64; output_count];
+        let mut output_cnt = output_count as u32;
+
+        self.call_async(callback, |wake_port, reference| unsafe {
+            IOConnectCallAsyncScalarMethod(
+                self.connect,
+                selector,
+                wake_port,
+                reference,
+                kOSAsyncRef64Count,
+                input.as_ptr(),
+                input.len() as u32,
+                output.as_mut_ptr(),
+                &mut output_cnt,
+            )
+        })?;
+
+        output.truncate(output_cnt as usize);
+
+        Ok(AsyncCall {
+            output: MethodOutput {
+                scalars: output,
+                structure: Vec::new(),
+            },
+            completion,
+        })
+    }
+
+    /// Starts an asynchronous method taking and returning only a structure.
+    pub fn call_async_struct(
+        &self,
+        selector: u32,
+        input: &[u8],
+        output_size: usize,
+    ) -> Result<AsyncCall, Error> {
+        let (callback, completion) = AsyncCall::completion();
+        let mut output = vec![0u8; output_size];
+        let mut output_cnt = output_size;
+
+        self.call_async(callback, |wake_port, reference| unsafe {
+            IOConnectCallAsyncStructMethod(
+                self.connect,
+                selector,
+                wake_port,
+                reference,
+                kOSAsyncRef64Count,
+                struct_ptr(input),
+                input.len(),
+                struct_mut_ptr(&mut output),
+                &mut output_cnt,
+            )
+        })?;
+
+        output.truncate(output_cnt);
+
+        Ok(AsyncCall {
+            output: MethodOutput {
+                scalars: Vec::new(),
+                structure: output,
+            },
+            completion,
+        })
+    }
+
+    /// Starts an asynchronous method taking and returning both scalars and a
+    /// structure.
+    pub fn call_async_method(
+        &self,
+        selector: u32,
+        scalar_input: &[u64],
+        struct_input: &[u8],
+        scalar_output_count: usize,
+        struct_output_size: usize,
+    ) -> Result<AsyncCall, Error> {
+        let (callback, completion) = AsyncCall::completion();
+
+        let output = self.call_async_method_with(
+            selector,
+            scalar_input,
+            struct_input,
+            scalar_output_count,
+            struct_output_size,
+            callback,
+        )?;
+
+        Ok(AsyncCall { output, completion })
+    }
+
+    /// Starts an asynchronous method, calling `callback` on the background
+    /// run loop thread when it completes. Returns the outputs available
+    /// immediately.
+    pub fn call_async_method_with<F>(
+        &self,
+        selector: u32,
+        scalar_input: &[u64],
+        struct_input: &[u8],
+        scalar_output_count: usize,
+        struct_output_size: usize,
+        callback: F,
+    ) -> Result<MethodOutput, Error>
+    where
+        F: FnOnce(AsyncCompletion) + Send + 'static,
+    {
+        let mut scalars = vec![0u64; scalar_output_count];
+        let mut scalars_cnt = scalar_output_count as u32;
+        let mut structure = vec![0u8; struct_output_size];
+        let mut structure_cnt = struct_output_size;
+
+        self.call_async(Box::new(callback), |wake_port, reference| unsafe {
+            IOConnectCallAsyncMethod(
+                self.connect,
+                selector,
+                wake_port,
+                reference,
+                kOSAsyncRef64Count,
+                scalar_input.as_ptr(),
+                scalar_input.len() as u32,
+                struct_ptr(struct_input),
+                struct_input.len(),
+                scalars.as_mut_ptr(),
+                &mut scalars_cnt,
+                struct_mut_ptr(&mut structure),
+                &mut structure_cnt,
+            )
+        })?;
+
+        scalars.truncate(scalars_cnt as usize);
+        structure.truncate(structure_cnt);
+
+        Ok(MethodOutput { scalars, structure })
+    }
+
+    /// Registers `callback` as a pending call and makes the call with a
+    /// reference array that routes its completion back to it.
+    fn call_async<C>(&self, callback: AsyncCallback, call: C) -> Result<(), Error>
+    where
+        C: FnOnce(mach_port_t, *mut u64) -> IOReturn,
+    {
+        let wake_port = self.get_wake_port()?;
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+
+        PENDING_CALLS
+            .lock()
+            .unwrap()
+            .insert(id, (self.id, callback));
+
+        // the kernel keeps its own copy of the reference array
+        let mut reference: OSAsyncReference64 = [0; kOSAsyncRef64Count as usize];
+        reference[kIOAsyncCalloutFuncIndex] = async_callback as IOAsyncCallback as usize as u64;
+        reference[kIOAsyncCalloutRefconIndex] = id;
+
+        let result = call(wake_port, reference.as_mut_ptr());
+
+        if result == kIOReturnSuccess {
+            Ok(())
+        } else {
+            PENDING_CALLS.lock().unwrap().remove(&id);
+            Err(Error::new(result))
+        }
+    }
+
+    fn get_wake_port(&self) -> Result<mach_port_t, Error> {
+        if let Some(&(_, wake_port)) = self.async_port.get() {
+            return Ok(wake_port);
+        }
+
+        let port = BackgroundRunLoop::get().create(|run_loop| {
+            let port = NotificationPort::new().ok_or(Error::new(kIOReturnNoMemory))?;
+            port.schedule_with_run_loop(run_loop)?;
+            Ok::<_, Error>(port)
+        })?;
+        let wake_port = port.with(|port| port.get_mach_port());
+
+        let _ = self.async_port.set((port, wake_port));
+        Ok(wake_port)
+    }
+}
+
+// IOKit expects NULL rather than a dangling pointer for empty structures
+fn struct_ptr(data: &[u8]) -> *const c_void {
+    if data.is_empty() {
+        ptr::null()
+    } else {
+        data.as_ptr() as *const c_void
+    }
+}
+
+fn struct_mut_ptr(data: &mut [u8]) -> *mut c_void {
+    if data.is_empty() {
+        ptr::null_mut()
+    } else {
+        data.as_mut_ptr() as *mut c_void
+    }
+}
+
+unsafe extern "C" fn async_callback(
+    refcon: *mut c_void,
+    result: IOReturn,
+    args: *mut *mut c_void,
+    num_args: u32,
+) {
+    let pending = PENDING_CALLS.lock().unwrap().remove(&(refcon as u64));
+
+    if let Some((_, callback)) = pending {
+        // completions of 64-bit references carry 64-bit arguments
+        let args = if args.is_null() {
+            Vec::new()
+        } else {
+            std::slice::from_raw_parts(args as *const u64, num_args as usize).to_vec()
+        };
+
+        let result = if result == kIOReturnSuccess {
+            Ok(())
+        } else {
+            Err(Error::new(result))
+        };
+
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+            callback(AsyncCompletion { result, args })
+        }));
+    }
+}
+
+struct Completion {
+    state: Mutex<(Option<AsyncCompletion>, Option<Waker>)>,
+}
+
+/// A pending asynchronous call, resolving to its completion.
+pub struct AsyncCall {
+    output: MethodOutput,
+    completion: Arc<Completion>,
+}
+
+impl AsyncCall {
+    fn completion() -> (AsyncCallback, Arc<Completion>) {
+        let completion = Arc::new(Completion {
+            state: Mutex::new((None, None)),
+        });
+        let sender = completion.clone();
+
+        let callback = Box::new(move |result| {
+            let waker = {
+                let mut state = sender.state.lock().unwrap();
+                state.0 = Some(result);
+                state.1.take()
+            };
+
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        });
+
+        (callback, completion)
+    }
+
+    /// The outputs the driver returned when the call was made.
+    pub fn output(&self) -> &MethodOutput {
+        &self.output
+    }
+}
+
+impl Future for AsyncCall {
+    type Output = AsyncCompletion;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<AsyncCompletion> {
+        let mut state = self.completion.state.lock().unwrap();
+
+        match state.0.take() {
+            Some(completion) => Poll::Ready(completion),
+            None => {
+                state.1 = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
@@ -0,0 +1,527 @@
+//! One error type for the `IOReturn` and `kern_return_t` codes returned by
+//! IOKit and Mach calls.
+
+use std::error;
+use std::fmt;
+
+use io_kit_sys::ret::*;
+
+use crate::mach::kern_return::*;
+use crate::mach::KernReturn;
+
+/// An `IOReturn` or `kern_return_t` code other than success.
+///
+/// Codes are laid out as `system << 26 | subsystem << 14 | code`; IOKit
+/// codes have system `0x38`, Mach kernel codes have system and subsystem 0.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Error(IOReturn);
+
+impl Error {
+    pub fn new(code: IOReturn) -> Error {
+        Error(code)
+    }
+
+    /// Returns the raw code.
+    pub fn raw(&self) -> IOReturn {
+        self.0
+    }
+
+    pub fn system(&self) -> u32 {
+        (self.0 as u32 >> 26) & 0x3f
+    }
+
+    pub fn subsystem(&self) -> u32 {
+        (self.0 as u32 >> 14) & 0xfff
+    }
+
+    pub fn code(&self) -> u32 {
+        self.0 as u32 & 0x3fff
+    }
+
+    /// Returns the name of the IOKit subsystem the code belongs to, such as
+    /// `"usb"` or `"hidsystem"`.
+    pub fn subsystem_name(&self) -> Option<&'static str> {
+        if self.0 & (0x3f << 26) != SYS_IOKIT {
+            return None;
+        }
+
+        let name = match self.0 & (0xfff << 14) {
+            SUB_IOKIT_COMMON => "common",
+            SUB_IOKIT_USB => "usb",
+            SUB_IOKIT_FIREWIRE => "firewire",
+            SUB_IOKIT_BLOCK_STORAGE => "block_storage",
+            SUB_IOKIT_GRAPHICS => "graphics",
+            SUB_IOKIT_NETWORKING => "networking",
+            SUB_IOKIT_BLUETOOTH => "bluetooth",
+            SUB_IOKIT_PMU => "pmu",
+            SUB_IOKIT_ACPI => "acpi",
+            SUB_IOKIT_SMBUS => "smbus",
+            SUB_IOKIT_AHCI => "ahci",
+            SUB_IOKIT_POWERMANAGEMENT => "powermanagement",
+            SUB_IOKIT_HIDSYSTEM => "hidsystem",
+            SUB_IOKIT_SCSI => "scsi",
+            SUB_IOKIT_USBAUDIO => "usbaudio",
+            SUB_IOKIT_PCCARD => "pccard",
+            SUB_IOKIT_THUNDERBOLT => "thunderbolt",
+            SUB_IOKIT_VENDOR_SPECIFIC => "vendor_specific",
+            SUB_IOKIT_RESERVED => "reserved",
+            _ => return None,
+        };
+
+        Some(name)
+    }
+
+    /// Returns the name of the constant for the code, if known. The DriverKit
+    /// USB codes share their values with the USB family, whose names are
+    /// returned for them. No header defines codes in the PC Card or power
+    /// management subsystems, so those only have a subsystem name; the return
+    /// codes of `io_kit_sys::pwr_mgt::pm` are plain values named as the Mach
+    /// codes they overlap.
+    pub fn name(&self) -> Option<&'static str> {
+        name(self.0)
+    }
+
+    /// Decodes a Mach kernel code.
+    pub fn kern_return(&self) -> Option<KernReturn> {
+        if self.system() == 0 && self.subsystem() == 0 {
+            Some(KernReturn::from(self.0))
+        } else {
+            None
+        }
+    }
+
+    pub fn is_timeout(&self) -> bool {
+        self.0 == kIOReturnTimeout
+            || self.0 == kIOUSBTransactionTimeout
+            || self.0 == KERN_OPERATION_TIMED_OUT
+    }
+}
+
+impl From<IOReturn> for Error {
+    fn from(code: IOReturn) -> Error {
+        Error(code)
+    }
+}
+
+impl From<Error> for IOReturn {
+    fn from(error: Error) -> IOReturn {
+        error.0
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{} ({:#010x})", name, self.0),
+            None => match self.subsystem_name() {
+                Some(subsystem) => write!(
+                    f,
+                    "{:#010x} (IOKit {} error {:#x})",
+                    self.0,
+                    subsystem,
+                    self.code()
+                ),
+                None => write!(
+                    f,
+                    "{:#010x} (system {:#x}, subsystem {:#x}, code {:#x})",
+                    self.0,
+                    self.system(),
+                    self.subsystem(),
+                    self.code()
+                ),
+            },
+        }
+    }
+}
+
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "Error({})", name),
+            None => write!(f, "Error({:#010x})", self.0),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// The error of `IOService::wait_quiet` and `io_kit_wait_quiet`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WaitError {
+    /// The timeout passed while something was still busy.
+    Timeout,
+    /// The wait failed for another reason, or the timeout was too long.
+    Error(Error),
+}
+
+impl From<Error> for WaitError {
+    fn from(error: Error) -> WaitError {
+        if error.is_timeout() {
+            WaitError::Timeout
+        } else {
+            WaitError::Error(error)
+        }
+    }
+}
+
+impl fmt::Display for WaitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WaitError::Timeout => write!(f, "timed out waiting for IOKit to be quiet"),
+            WaitError::Error(error) => error.fmt(f),
+        }
+    }
+}
+
+impl error::Error for WaitError {}
+
+fn name(code: IOReturn) -> Option<&'static str> {
+    let name = match code {
+        KERN_SUCCESS => "KERN_SUCCESS",
+        KERN_INVALID_ADDRESS => "KERN_INVALID_ADDRESS",
+        KERN_PROTECTION_FAILURE => "KERN_PROTECTION_FAILURE",
+        KERN_NO_SPACE => "KERN_NO_SPACE",
+        KERN_INVALID_ARGUMENT => "KERN_INVALID_ARGUMENT",
+        KERN_FAILURE => "KERN_FAILURE",
+        KERN_RESOURCE_SHORTAGE => "KERN_RESOURCE_SHORTAGE",
+        KERN_NOT_RECEIVER => "KERN_NOT_RECEIVER",
+        KERN_NO_ACCESS => "KERN_NO_ACCESS",
+        KERN_MEMORY_FAILURE => "KERN_MEMORY_FAILURE",
+        KERN_MEMORY_ERROR => "KERN_MEMORY_ERROR",
+        KERN_ALREADY_IN_SET => "KERN_ALREADY_IN_SET",
+        KERN_NOT_IN_SET => "KERN_NOT_IN_SET",
+        KERN_NAME_EXISTS => "KERN_NAME_EXISTS",
+        KERN_ABORTED => "KERN_ABORTED",
+        KERN_INVALID_NAME => "KERN_INVALID_NAME",
+        KERN_INVALID_TASK => "KERN_INVALID_TASK",
+        KERN_INVALID_RIGHT => "KERN_INVALID_RIGHT",
+        KERN_INVALID_VALUE => "KERN_INVALID_VALUE",
+        KERN_UREFS_OVERFLOW => "KERN_UREFS_OVERFLOW",
+        KERN_INVALID_CAPABILITY => "KERN_INVALID_CAPABILITY",
+        KERN_RIGHT_EXISTS => "KERN_RIGHT_EXISTS",
+        KERN_INVALID_HOST => "KERN_INVALID_HOST",
+        KERN_MEMORY_PRESENT => "KERN_MEMORY_PRESENT",
+        KERN_MEMORY_DATA_MOVED => "KERN_MEMORY_DATA_MOVED",
+        KERN_MEMORY_RESTART_COPY => "KERN_MEMORY_RESTART_COPY",
+        KERN_INVALID_PROCESSOR_SET => "KERN_INVALID_PROCESSOR_SET",
+        KERN_POLICY_LIMIT => "KERN_POLICY_LIMIT",
+        KERN_INVALID_POLICY => "KERN_INVALID_POLICY",
+        KERN_INVALID_OBJECT => "KERN_INVALID_OBJECT",
+        KERN_ALREADY_WAITING => "KERN_ALREADY_WAITING",
+        KERN_DEFAULT_SET => "KERN_DEFAULT_SET",
+        KERN_EXCEPTION_PROTECTED => "KERN_EXCEPTION_PROTECTED",
+        KERN_INVALID_LEDGER => "KERN_INVALID_LEDGER",
+        KERN_INVALID_MEMORY_CONTROL => "KERN_INVALID_MEMORY_CONTROL",
+        KERN_INVALID_SECURITY => "KERN_INVALID_SECURITY",
+        KERN_NOT_DEPRESSED => "KERN_NOT_DEPRESSED",
+        KERN_TERMINATED => "KERN_TERMINATED",
+        KERN_LOCK_SET_DESTROYED => "KERN_LOCK_SET_DESTROYED",
+        KERN_LOCK_UNSTABLE => "KERN_LOCK_UNSTABLE",
+        KERN_LOCK_OWNED => "KERN_LOCK_OWNED",
+        KERN_LOCK_OWNED_SELF => "KERN_LOCK_OWNED_SELF",
+        KERN_SEMAPHORE_DESTROYED => "KERN_SEMAPHORE_DESTROYED",
+        KERN_RPC_SERVER_TERMINATED => "KERN_RPC_SERVER_TERMINATED",
+        KERN_RPC_TERMINATE_ORPHAN => "KERN_RPC_TERMINATE_ORPHAN",
+        KERN_RPC_CONTINUE_ORPHAN => "KERN_RPC_CONTINUE_ORPHAN",
+        KERN_NOT_SUPPORTED => "KERN_NOT_SUPPORTED",
+        KERN_NODE_DOWN => "KERN_NODE_DOWN",
+        KERN_NOT_WAITING => "KERN_NOT_WAITING",
+        KERN_OPERATION_TIMED_OUT => "KERN_OPERATION_TIMED_OUT",
+        kIOReturnError => "kIOReturnError",
+        kIOReturnNoMemory => "kIOReturnNoMemory",
+        kIOReturnNoResources => "kIOReturnNoResources",
+        kIOReturnIPCError => "kIOReturnIPCError",
+        kIOReturnNoDevice => "kIOReturnNoDevice",
+        kIOReturnNotPrivileged => "kIOReturnNotPrivileged",
+        kIOReturnBadArgument => "kIOReturnBadArgument",
+        kIOReturnLockedRead => "kIOReturnLockedRead",
+        kIOReturnLockedWrite => "kIOReturnLockedWrite",
+        kIOReturnExclusiveAccess => "kIOReturnExclusiveAccess",
+        kIOReturnBadMessageID => "kIOReturnBadMessageID",
+        kIOReturnUnsupported => "kIOReturnUnsupported",
+        kIOReturnVMError => "kIOReturnVMError",
+        kIOReturnInternalError => "kIOReturnInternalError",
+        kIOReturnIOError => "kIOReturnIOError",
+        kIOReturnCannotLock => "kIOReturnCannotLock",
+        kIOReturnNotOpen => "kIOReturnNotOpen",
+        kIOReturnNotReadable => "kIOReturnNotReadable",
+        kIOReturnNotWritable => "kIOReturnNotWritable",
+        kIOReturnNotAligned => "kIOReturnNotAligned",
+        kIOReturnBadMedia => "kIOReturnBadMedia",
+        kIOReturnStillOpen => "kIOReturnStillOpen",
+        kIOReturnRLDError => "kIOReturnRLDError",
+        kIOReturnDMAError => "kIOReturnDMAError",
+        kIOReturnBusy => "kIOReturnBusy",
+        kIOReturnTimeout => "kIOReturnTimeout",
+        kIOReturnOffline => "kIOReturnOffline",
+        kIOReturnNotReady => "kIOReturnNotReady",
+        kIOReturnNotAttached => "kIOReturnNotAttached",
+        kIOReturnNoChannels => "kIOReturnNoChannels",
+        kIOReturnNoSpace => "kIOReturnNoSpace",
+        kIOReturnPortExists => "kIOReturnPortExists",
+        kIOReturnCannotWire => "kIOReturnCannotWire",
+        kIOReturnNoInterrupt => "kIOReturnNoInterrupt",
+        kIOReturnNoFrames => "kIOReturnNoFrames",
+        kIOReturnMessageTooLarge => "kIOReturnMessageTooLarge",
+        kIOReturnNotPermitted => "kIOReturnNotPermitted",
+        kIOReturnNoPower => "kIOReturnNoPower",
+        kIOReturnNoMedia => "kIOReturnNoMedia",
+        kIOReturnUnformattedMedia => "kIOReturnUnformattedMedia",
+        kIOReturnUnsupportedMode => "kIOReturnUnsupportedMode",
+        kIOReturnUnderrun => "kIOReturnUnderrun",
+        kIOReturnOverrun => "kIOReturnOverrun",
+        kIOReturnDeviceError => "kIOReturnDeviceError",
+        kIOReturnNoCompletion => "kIOReturnNoCompletion",
+        kIOReturnAborted => "kIOReturnAborted",
+        kIOReturnNoBandwidth => "kIOReturnNoBandwidth",
+        kIOReturnNotResponding => "kIOReturnNotResponding",
+        kIOReturnIsoTooOld => "kIOReturnIsoTooOld",
+        kIOReturnIsoTooNew => "kIOReturnIsoTooNew",
+        kIOReturnNotFound => "kIOReturnNotFound",
+        kIOReturnInvalid => "kIOReturnInvalid",
+        kIOUSBCRCErr => "kIOUSBCRCErr",
+        kIOUSBBitstufErr => "kIOUSBBitstufErr",
+        kIOUSBDataToggleErr => "kIOUSBDataToggleErr",
+        kIOUSBPIDCheckErr => "kIOUSBPIDCheckErr",
+        kIOUSBWrongPIDErr => "kIOUSBWrongPIDErr",
+        kIOUSBReserved1Err => "kIOUSBReserved1Err",
+        kIOUSBReserved2Err => "kIOUSBReserved2Err",
+        kIOUSBBufferOverrunErr => "kIOUSBBufferOverrunErr",
+        kIOUSBBufferUnderrunErr => "kIOUSBBufferUnderrunErr",
+        kIOUSBNotSent1Err => "kIOUSBNotSent1Err",
+        kIOUSBNotSent2Err => "kIOUSBNotSent2Err",
+        kIOUSBLinkErr => "kIOUSBLinkErr",
+        kIOUSBClearPipeStallNotRecursive => "kIOUSBClearPipeStallNotRecursive",
+        kIOUSBDeviceNotHighSpeed => "kIOUSBDeviceNotHighSpeed",
+        kIOUSBSyncRequestOnWLThread => "kIOUSBSyncRequestOnWLThread",
+        kIOUSBHighSpeedSplitError => "kIOUSBHighSpeedSplitError",
+        kIOUSBLowLatencyFrameListNotPreviouslyAllocated => {
+            "kIOUSBLowLatencyFrameListNotPreviouslyAllocated"
+        }
+        kIOUSBLowLatencyBufferNotPreviouslyAllocated => {
+            "kIOUSBLowLatencyBufferNotPreviouslyAllocated"
+        }
+        kIOUSBInterfaceNotFound => "kIOUSBInterfaceNotFound",
+        kIOUSBPipeStalled => "kIOUSBPipeStalled",
+        kIOUSBTransactionReturned => "kIOUSBTransactionReturned",
+        kIOUSBTransactionTimeout => "kIOUSBTransactionTimeout",
+        kIOUSBPortWasSuspended => "kIOUSBPortWasSuspended",
+        kIOUSBConfigNotFound => "kIOUSBConfigNotFound",
+        kIOUSBEndpointNotFound => "kIOUSBEndpointNotFound",
+        kIOUSBNotEnoughPowerErr => "kIOUSBNotEnoughPowerErr",
+        kIOUSBNotEnoughPipesErr => "kIOUSBNotEnoughPipesErr",
+        kIOUSBNoAsyncPortErr => "kIOUSBNoAsyncPortErr",
+        kIOUSBTooManyPipesErr => "kIOUSBTooManyPipesErr",
+        kIOUSBUnknownPipeErr => "kIOUSBUnknownPipeErr",
+        kIOFireWirePending => "kIOFireWirePending",
+        kIOFireWireLastDCLToken => "kIOFireWireLastDCLToken",
+        kIOFireWireConfigROMInvalid => "kIOFireWireConfigROMInvalid",
+        kIOFireWireAlreadyRegistered => "kIOFireWireAlreadyRegistered",
+        kIOFireWireMultipleTalkers => "kIOFireWireMultipleTalkers",
+        kIOFireWireChannelActive => "kIOFireWireChannelActive",
+        kIOFireWireNoListenerOrTalker => "kIOFireWireNoListenerOrTalker",
+        kIOFireWireNoChannels => "kIOFireWireNoChannels",
+        kIOFireWireChannelNotAvailable => "kIOFireWireChannelNotAvailable",
+        kIOFireWireSeparateBus => "kIOFireWireSeparateBus",
+        kIOFireWireBadSelfIDs => "kIOFireWireBadSelfIDs",
+        kIOFireWireLowCableVoltage => "kIOFireWireLowCableVoltage",
+        kIOFireWireInsufficientPower => "kIOFireWireInsufficientPower",
+        kIOFireWireOutOfTLabels => "kIOFireWireOutOfTLabels",
+        kIOBluetoothDeviceResetError => "kIOBluetoothDeviceResetError",
+        kIOBluetoothConnectionAlreadyExists => "kIOBluetoothConnectionAlreadyExists",
+        kIOBluetoothNoHCIController => "kIOBluetoothNoHCIController",
+        kIOBluetoothHCIPowerStatesNotSupported => "kIOBluetoothHCIPowerStatesNotSupported",
+        kHIDBaseError => "kHIDBaseError",
+        kHIDNullStateErr => "kHIDNullStateErr",
+        kHIDBufferTooSmallErr => "kHIDBufferTooSmallErr",
+        kHIDValueOutOfRangeErr => "kHIDValueOutOfRangeErr",
+        kHIDUsageNotFoundErr => "kHIDUsageNotFoundErr",
+        kHIDNotValueArrayErr => "kHIDNotValueArrayErr",
+        kHIDInvalidPreparsedDataErr => "kHIDInvalidPreparsedDataErr",
+        kHIDIncompatibleReportErr => "kHIDIncompatibleReportErr",
+        kHIDBadLogPhysValuesErr => "kHIDBadLogPhysValuesErr",
+        kHIDInvalidReportTypeErr => "kHIDInvalidReportTypeErr",
+        kHIDInvalidReportLengthErr => "kHIDInvalidReportLengthErr",
+        kHIDNullPointerErr => "kHIDNullPointerErr",
+        kHIDBadParameterErr => "kHIDBadParameterErr",
+        kHIDNotEnoughMemoryErr => "kHIDNotEnoughMemoryErr",
+        kHIDEndOfDescriptorErr => "kHIDEndOfDescriptorErr",
+        kHIDUsagePageZeroErr => "kHIDUsagePageZeroErr",
+        kHIDBadLogicalMinimumErr => "kHIDBadLogicalMinimumErr",
+        kHIDBadLogicalMaximumErr => "kHIDBadLogicalMaximumErr",
+        kHIDInvertedLogicalRangeErr => "kHIDInvertedLogicalRangeErr",
+        kHIDInvertedPhysicalRangeErr => "kHIDInvertedPhysicalRangeErr",
+        kHIDUnmatchedUsageRangeErr => "kHIDUnmatchedUsageRangeErr",
+        kHIDInvertedUsageRangeErr => "kHIDInvertedUsageRangeErr",
+        kHIDUnmatchedStringRangeErr => "kHIDUnmatchedStringRangeErr",
+        kHIDUnmatchedDesignatorRangeErr => "kHIDUnmatchedDesignatorRangeErr",
+        kHIDReportSizeZeroErr => "kHIDReportSizeZeroErr",
+        kHIDReportCountZeroErr => "kHIDReportCountZeroErr",
+        kHIDReportIDZeroErr => "kHIDReportIDZeroErr",
+        kHIDInvalidRangePageErr => "kHIDInvalidRangePageErr",
+        _ => return None,
+    };
+
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout() {
+        let error = Error::new(kIOUSBPipeStalled);
+
+        assert_eq!(error.raw() as u32, 0xe000_404f);
+        assert_eq!(error.system(), 0x38);
+        assert_eq!(error.subsystem(), 1);
+        assert_eq!(error.code(), 0x4f);
+        assert_eq!(IOReturn::from(error), kIOUSBPipeStalled);
+    }
+
+    #[test]
+    fn names() {
+        let names = [
+            (
+                kIOReturnNotPrivileged,
+                "kIOReturnNotPrivileged",
+                Some("common"),
+            ),
+            (kIOReturnInvalid, "kIOReturnInvalid", Some("common")),
+            (kIOUSBPipeStalled, "kIOUSBPipeStalled", Some("usb")),
+            (kIOUSBCRCErr, "kIOUSBCRCErr", Some("usb")),
+            (
+                kUSBHostReturnNoPower,
+                "kIOUSBNotEnoughPowerErr",
+                Some("usb"),
+            ),
+            (kIOFireWirePending, "kIOFireWirePending", Some("firewire")),
+            (
+                kIOBluetoothNoHCIController,
+                "kIOBluetoothNoHCIController",
+                Some("bluetooth"),
+            ),
+            (kHIDBaseError, "kHIDBaseError", None),
+            (
+                kHIDInvalidReportLengthErr,
+                "kHIDInvalidReportLengthErr",
+                None,
+            ),
+            (kHIDInvalidRangePageErr, "kHIDInvalidRangePageErr", None),
+            (KERN_INVALID_ARGUMENT, "KERN_INVALID_ARGUMENT", None),
+        ];
+
+        for &(code, name, subsystem) in &names {
+            let error = Error::new(code);
+
+            assert_eq!(error.name(), Some(name));
+            assert_eq!(error.subsystem_name(), subsystem);
+            assert_eq!(error.to_string(), format!("{} ({:#010x})", name, code));
+            assert_eq!(format!("{:?}", error), format!("Error({})", name));
+        }
+
+        assert_eq!(kIOUSBPipeStalled as u32, 0xe000_404f);
+        assert_eq!(kIOBluetoothDeviceResetError as u32, 0xe002_0001);
+        assert_eq!(kHIDInvalidReportLengthErr, -13940);
+    }
+
+    #[test]
+    fn power_management_codes() {
+        use io_kit_sys::pwr_mgt::pm::{kIOPMNoSuchState, kIOPMNotYetInitialized};
+
+        assert_eq!(
+            Error::new(kIOPMNoSuchState as IOReturn).name(),
+            Some("KERN_FAILURE")
+        );
+        assert_eq!(
+            Error::new(kIOPMNotYetInitialized as IOReturn).name(),
+            Some("KERN_NO_ACCESS")
+        );
+        assert_eq!(
+            Error::new(SYS_IOKIT | SUB_IOKIT_POWERMANAGEMENT | 0x1).to_string(),
+            "0xe0034001 (IOKit powermanagement error 0x1)"
+        );
+    }
+
+    #[test]
+    fn unknown_codes() {
+        let subsystems = [
+            (SUB_IOKIT_COMMON, "common"),
+            (SUB_IOKIT_USB, "usb"),
+            (SUB_IOKIT_FIREWIRE, "firewire"),
+            (SUB_IOKIT_BLOCK_STORAGE, "block_storage"),
+            (SUB_IOKIT_GRAPHICS, "graphics"),
+            (SUB_IOKIT_NETWORKING, "networking"),
+            (SUB_IOKIT_BLUETOOTH, "bluetooth"),
+            (SUB_IOKIT_PMU, "pmu"),
+            (SUB_IOKIT_ACPI, "acpi"),
+            (SUB_IOKIT_SMBUS, "smbus"),
+            (SUB_IOKIT_AHCI, "ahci"),
+            (SUB_IOKIT_POWERMANAGEMENT, "powermanagement"),
+            (SUB_IOKIT_HIDSYSTEM, "hidsystem"),
+            (SUB_IOKIT_SCSI, "scsi"),
+            (SUB_IOKIT_USBAUDIO, "usbaudio"),
+            (SUB_IOKIT_PCCARD, "pccard"),
+            (SUB_IOKIT_THUNDERBOLT, "thunderbolt"),
+            (SUB_IOKIT_VENDOR_SPECIFIC, "vendor_specific"),
+            (SUB_IOKIT_RESERVED, "reserved"),
+        ];
+
+        for &(subsystem, label) in &subsystems {
+            let error = Error::new(SYS_IOKIT | subsystem | 0x3ff0);
+
+            assert_eq!(error.name(), None);
+            assert_eq!(error.subsystem_name(), Some(label));
+            assert_eq!(error.code(), 0x3ff0);
+            assert_eq!(
+                error.to_string(),
+                format!("{:#010x} (IOKit {} error 0x3ff0)", error.raw(), label)
+            );
+        }
+
+        // an IOKit subsystem with no name
+        let error = Error::new(SYS_IOKIT | (3 << 14) | 1);
+        assert_eq!(error.subsystem_name(), None);
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "{:#010x} (system 0x38, subsystem 0x3, code 0x1)",
+                error.raw()
+            )
+        );
+
+        // a code of another system
+        let error = Error::new(0x1000_0003);
+        assert_eq!(error.name(), None);
+        assert_eq!(error.subsystem_name(), None);
+        assert_eq!(error.kern_return(), None);
+        assert_eq!(error.system(), 4);
+    }
+
+    #[test]
+    fn kernel_codes() {
+        assert_eq!(
+            Error::new(KERN_INVALID_ARGUMENT).kern_return(),
+            Some(KernReturn::from(KERN_INVALID_ARGUMENT))
+        );
+        assert_eq!(Error::new(kIOReturnError).kern_return(), None);
+    }
+
+    #[test]
+    fn timeouts() {
+        assert!(Error::new(kIOReturnTimeout).is_timeout());
+        assert!(Error::new(kIOUSBTransactionTimeout).is_timeout());
+        assert!(Error::new(KERN_OPERATION_TIMED_OUT).is_timeout());
+        assert!(!Error::new(kIOReturnBusy).is_timeout());
+
+        assert_eq!(
+            WaitError::from(Error::new(kIOReturnTimeout)),
+            WaitError::Timeout
+        );
+        assert_eq!(
+            WaitError::from(Error::new(kIOReturnBusy)),
+            WaitError::Error(Error::new(kIOReturnBusy))
+        );
+    }
+}
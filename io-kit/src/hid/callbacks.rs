@@ -0,0 +1,47 @@
+//! The context each HID object's callbacks were last registered with. Any
+//! number of wrappers may share one CF object, but IOKit keeps one callback
+//! of each kind per object, so a wrapper must only unregister a callback
+//! that has not since been replaced through another wrapper.
+
+use std::collections::BTreeMap;
+use std::os::raw::c_void;
+use std::sync::Mutex;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Slot {
+    DeviceMatching,
+    DeviceRemoval,
+    InputValue,
+    InputReport,
+}
+
+// keyed by the addresses of the object, valued by those of the contexts
+static CONTEXTS: Mutex<BTreeMap<(usize, Slot), usize>> = Mutex::new(BTreeMap::new());
+
+/// Calls `register`, which hands `context` to IOKit, and records it as the
+/// current context of `slot` on `object`.
+pub(crate) fn register<F>(object: *const c_void, slot: Slot, context: *mut c_void, register: F)
+where
+    F: FnOnce(),
+{
+    let mut contexts = CONTEXTS.lock().unwrap();
+
+    register();
+    contexts.insert((object as usize, slot), context as usize);
+}
+
+/// Calls `unregister` if `context` is still the current context of `slot`
+/// on `object`. Either way IOKit no longer uses `context` afterwards, except
+/// through callbacks that cannot be unregistered, such as input reports.
+pub(crate) fn unregister<F>(object: *const c_void, slot: Slot, context: *mut c_void, unregister: F)
+where
+    F: FnOnce(),
+{
+    let mut contexts = CONTEXTS.lock().unwrap();
+    let key = (object as usize, slot);
+
+    if contexts.get(&key) == Some(&(context as usize)) {
+        unregister();
+        contexts.remove(&key);
+    }
+}
@@ -1,21 +1,100 @@
-use std::os::raw::c_char;
+use std::os::raw::{c_char, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
 
-use core_foundation::base::{kCFAllocatorDefault, CFRelease, CFType, CFTypeID, TCFType};
+use core_foundation::array::CFArray;
+use core_foundation::base::{
+    kCFAllocatorDefault, CFRelease, CFRetain, CFType, CFTypeID, CFTypeRef, TCFType,
+};
+use core_foundation::runloop::CFRunLoop;
+use core_foundation::string::{CFString, CFStringRef};
+use core_foundation::ConcreteCFType;
 
 pub use io_kit_sys::hid::base::IOHIDDeviceRef;
 pub use io_kit_sys::hid::device::*;
-use io_kit_sys::hid::keys::kIOHIDOptionsTypeNone;
-use io_kit_sys::CFSTR;
+use io_kit_sys::hid::keys::{kIOHIDOptionsTypeNone, IOHIDReportType};
+use io_kit_sys::{IOObjectRetain, CFSTR};
+use mach::kern_return::KERN_SUCCESS;
 
 use crate::{
     base::{IOService, TIOObject},
-    ret::{kIOReturnSuccess, IOReturn},
+    error::Error,
+    hid::{
+        callbacks::{self, Slot},
+        element::{HidElementMatching, IOHIDElement},
+        value::{input_value_callback, no_input_value_callback, IOHIDValue},
+    },
+    keys::typed::kIOHIDMaxInputReportSizeKey,
+    property::TIOProperties,
+    ret::{kIOReturnError, kIOReturnSuccess, kIOReturnUnsupported, IOReturn},
 };
 
-pub struct IOHIDDevice(IOHIDDeviceRef);
+type InputValueCallback = dyn FnMut(IOHIDValue, IOHIDElement, IOHIDDevice);
+type InputReportCallback = dyn FnMut(IOHIDReportType, u32, &[u8]);
+type TimeStampedInputReportCallback = dyn FnMut(IOHIDReportType, u32, &[u8], u64);
+
+enum ReportCallback {
+    Plain(Box<InputReportCallback>),
+    TimeStamped(Box<TimeStampedInputReportCallback>),
+}
+
+// the context of a report callback; the callback is taken out when it is
+// unregistered, but the registration stays with IOKit
+struct InputReportRegistration {
+    callback: Option<ReportCallback>,
+    buffer: ReportBuffer,
+}
+
+// IOKit writes each report into the buffer before calling back, until
+// another buffer is registered or the device stops delivering reports
+struct ReportBuffer(*mut [u8]);
+
+impl ReportBuffer {
+    fn new(size: usize) -> ReportBuffer {
+        ReportBuffer(Box::into_raw(vec![0u8; size].into_boxed_slice()))
+    }
+
+    fn as_mut_ptr(&self) -> *mut u8 {
+        self.0 as *mut u8
+    }
+
+    fn len(&self) -> isize {
+        self.0.len() as isize
+    }
+}
+
+impl Drop for ReportBuffer {
+    fn drop(&mut self) {
+        unsafe { drop(Box::from_raw(self.0)) }
+    }
+}
+
+/// A HID device. The callbacks registered through it are owned by this value
+/// and unregistered when it is dropped, unless a clone or another value for
+/// the same device has since replaced them. The callbacks run on the run loop
+/// the device is scheduled on, which `HidEventLoop` does on a thread of its
+/// own, so a device with callbacks must be unscheduled, or dropped, on that
+/// run loop's thread.
+pub struct IOHIDDevice {
+    device: IOHIDDeviceRef,
+    input_value_callback: Option<Box<Box<InputValueCallback>>>,
+    input_report_callback: Option<Box<InputReportRegistration>>,
+}
 
 impl Drop for IOHIDDevice {
     fn drop(&mut self) {
+        // other references may keep the device alive past the callbacks
+        self.unregister_input_value_callback();
+
+        // the callers of the report registrations have made sure that no
+        // more reports are delivered
+        if let Some(mut registration) = self.input_report_callback.take() {
+            let context = &mut *registration as *mut InputReportRegistration as *mut c_void;
+
+            callbacks::unregister(self.as_CFTypeRef(), Slot::InputReport, context, || {});
+        }
+
         unsafe { CFRelease(self.as_CFTypeRef()) }
     }
 }
@@ -32,42 +111,67 @@ impl IOHIDDevice {
             if result.is_null() {
                 None
             } else {
-                Some(IOHIDDevice(result))
+                Some(IOHIDDevice::wrap_under_create_rule(result))
+            }
+        }
+    }
+
+    /// The registry entry of the device, which unlike the device itself may
+    /// be sent to other threads.
+    pub fn get_service(&self) -> Option<IOService> {
+        unsafe {
+            let service = IOHIDDeviceGetService(self.device);
+
+            if service != 0 && IOObjectRetain(service) == KERN_SUCCESS {
+                Some(IOService(service))
+            } else {
+                None
             }
         }
     }
 
-    pub fn open(&self) -> Result<(), IOReturn> {
+    pub fn open(&self) -> Result<(), Error> {
         unsafe {
-            let result = IOHIDDeviceOpen(self.0, kIOHIDOptionsTypeNone);
+            let result = IOHIDDeviceOpen(self.device, kIOHIDOptionsTypeNone);
 
             if result == kIOReturnSuccess {
                 Ok(())
             } else {
-                Err(result)
+                Err(Error::new(result))
             }
         }
     }
 
-    pub fn close(&self) -> Result<(), IOReturn> {
+    pub fn close(&self) -> Result<(), Error> {
         unsafe {
-            let result = IOHIDDeviceClose(self.0, kIOHIDOptionsTypeNone);
+            let result = IOHIDDeviceClose(self.device, kIOHIDOptionsTypeNone);
 
             if result == kIOReturnSuccess {
                 Ok(())
             } else {
-                Err(result)
+                Err(Error::new(result))
             }
         }
     }
 
+    /// Delivers the callbacks on `run_loop` while it runs in `mode`.
+    pub(crate) fn schedule_with_run_loop(&self, run_loop: &CFRunLoop, mode: CFStringRef) {
+        unsafe { IOHIDDeviceScheduleWithRunLoop(self.device, run_loop.as_concrete_TypeRef(), mode) }
+    }
+
+    pub(crate) fn unschedule_from_run_loop(&self, run_loop: &CFRunLoop, mode: CFStringRef) {
+        unsafe {
+            IOHIDDeviceUnscheduleFromRunLoop(self.device, run_loop.as_concrete_TypeRef(), mode)
+        }
+    }
+
     pub fn conforms_to(&self, usage_page: u32, usage: u32) -> bool {
-        unsafe { IOHIDDeviceConformsTo(self.0, usage_page, usage) != 0 }
+        unsafe { IOHIDDeviceConformsTo(self.device, usage_page, usage) != 0 }
     }
 
     pub fn get_property(&self, key: *const c_char) -> Option<CFType> {
         unsafe {
-            let result = IOHIDDeviceGetProperty(self.0, CFSTR(key));
+            let result = IOHIDDeviceGetProperty(self.device, CFSTR(key));
 
             if result.is_null() {
                 None
@@ -76,6 +180,284 @@ impl IOHIDDevice {
             }
         }
     }
+
+    /// Calls `callback` with each input value the device reports, once it is
+    /// opened and scheduled on a run loop. The callback runs on that run loop.
+    /// Replaces any previous callback.
+    pub fn register_input_value_callback<F>(&mut self, callback: F)
+    where
+        F: FnMut(IOHIDValue, IOHIDElement, IOHIDDevice) + 'static,
+    {
+        let mut callback: Box<Box<InputValueCallback>> = Box::new(Box::new(callback));
+        let context = &mut *callback as *mut Box<InputValueCallback> as *mut c_void;
+
+        callbacks::register(self.as_CFTypeRef(), Slot::InputValue, context, || unsafe {
+            IOHIDDeviceRegisterInputValueCallback(
+                self.device,
+                input_value_callback::<InputValueCallback>,
+                context,
+            )
+        });
+
+        self.input_value_callback = Some(callback);
+    }
+
+    pub fn unregister_input_value_callback(&mut self) {
+        if let Some(mut callback) = self.input_value_callback.take() {
+            let context = &mut *callback as *mut Box<InputValueCallback> as *mut c_void;
+
+            callbacks::unregister(self.as_CFTypeRef(), Slot::InputValue, context, || unsafe {
+                IOHIDDeviceRegisterInputValueCallback(
+                    self.device,
+                    no_input_value_callback,
+                    ptr::null_mut(),
+                )
+            });
+        }
+    }
+
+    /// Limits the input value callback to the elements matching `matching`.
+    pub fn set_input_value_matching(&self, matching: &HidElementMatching) {
+        let matching = matching.to_cf_dictionary();
+
+        unsafe { IOHIDDeviceSetInputValueMatching(self.device, matching.as_concrete_TypeRef()) }
+    }
+
+    /// Like `set_input_value_matching`, but an element may match any of
+    /// `matchings`. An empty slice matches every element.
+    pub fn set_input_value_matching_multiple(&self, matchings: &[HidElementMatching]) {
+        if matchings.is_empty() {
+            unsafe { IOHIDDeviceSetInputValueMatchingMultiple(self.device, ptr::null()) };
+            return;
+        }
+
+        let matchings = matchings
+            .iter()
+            .map(HidElementMatching::to_cf_dictionary)
+            .collect::<Vec<_>>();
+        let array = CFArray::from_CFTypes(&matchings);
+
+        unsafe {
+            IOHIDDeviceSetInputValueMatchingMultiple(self.device, array.as_concrete_TypeRef())
+        }
+    }
+
+    /// Calls `callback` with the type, ID and bytes of each input report,
+    /// once the device is opened and scheduled on a run loop. The report
+    /// buffer is sized from `kIOHIDMaxInputReportSizeKey`. Replaces any
+    /// previous report callback, with or without timestamps.
+    ///
+    /// # Safety
+    ///
+    /// IOKit has no way to unregister the report buffer, which this value
+    /// owns, and writes each report into it until another report callback is
+    /// registered for the device. Unless that has happened, or this value
+    /// holds the last reference to the device, the device must be closed or
+    /// unscheduled from its run loop before this value is dropped.
+    pub unsafe fn register_input_report_callback<F>(&mut self, callback: F) -> Result<(), Error>
+    where
+        F: FnMut(IOHIDReportType, u32, &[u8]) + 'static,
+    {
+        let mut registration =
+            self.new_report_registration(ReportCallback::Plain(Box::new(callback)))?;
+        let context = &mut *registration as *mut InputReportRegistration as *mut c_void;
+
+        callbacks::register(self.as_CFTypeRef(), Slot::InputReport, context, || {
+            IOHIDDeviceRegisterInputReportCallback(
+                self.device,
+                registration.buffer.as_mut_ptr(),
+                registration.buffer.len(),
+                input_report_callback,
+                context,
+            )
+        });
+
+        // the previous callback and buffer are only freed once IOKit has
+        // been given the new ones
+        self.input_report_callback = Some(registration);
+
+        Ok(())
+    }
+
+    /// Like `register_input_report_callback`, but also passes the
+    /// `mach_absolute_time` timestamp of each report.
+    ///
+    /// # Safety
+    ///
+    /// As for `register_input_report_callback`.
+    pub unsafe fn register_input_report_with_timestamp_callback<F>(
+        &mut self,
+        callback: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(IOHIDReportType, u32, &[u8], u64) + 'static,
+    {
+        let mut registration =
+            self.new_report_registration(ReportCallback::TimeStamped(Box::new(callback)))?;
+        let context = &mut *registration as *mut InputReportRegistration as *mut c_void;
+
+        callbacks::register(self.as_CFTypeRef(), Slot::InputReport, context, || {
+            IOHIDDeviceRegisterInputReportWithTimeStampCallback(
+                self.device,
+                registration.buffer.as_mut_ptr(),
+                registration.buffer.len(),
+                input_report_with_timestamp_callback,
+                context,
+            )
+        });
+
+        self.input_report_callback = Some(registration);
+
+        Ok(())
+    }
+
+    /// Drops the report callback, after which reports are ignored. The
+    /// report buffer stays registered, and allocated, until another report
+    /// callback is registered or this value is dropped.
+    pub fn unregister_input_report_callback(&mut self) {
+        if let Some(ref mut registration) = self.input_report_callback {
+            registration.callback = None;
+        }
+    }
+
+    fn new_report_registration(
+        &self,
+        callback: ReportCallback,
+    ) -> Result<Box<InputReportRegistration>, Error> {
+        match self.get(&kIOHIDMaxInputReportSizeKey) {
+            Some(size) if size > 0 => Ok(Box::new(InputReportRegistration {
+                callback: Some(callback),
+                buffer: ReportBuffer::new(size as usize),
+            })),
+            _ => Err(Error::new(kIOReturnUnsupported)),
+        }
+    }
+}
+unsafe extern "C" fn input_report_callback(
+    context: *mut c_void,
+    _result: IOReturn,
+    _sender: *mut c_void,
+    report_type: IOHIDReportType,
+    report_id: u32,
+    report: *mut u8,
+    report_length: isize,
+) {
+    let registration = &mut *(context as *mut InputReportRegistration);
+    let callback = match registration.callback {
+        Some(ReportCallback::Plain(ref mut callback)) => callback,
+        _ => return,
+    };
+    let report = report_slice(report, report_length);
+
+    // the panic hook has already reported the panic; it must not unwind
+    // into IOKit
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        callback(report_type, report_id, report)
+    }));
+}
+
+unsafe extern "C" fn input_report_with_timestamp_callback(
+    context: *mut c_void,
+    _result: IOReturn,
+    _sender: *mut c_void,
+    report_type: IOHIDReportType,
+    report_id: u32,
+    report: *mut u8,
+    report_length: isize,
+    timestamp: u64,
+) {
+    let registration = &mut *(context as *mut InputReportRegistration);
+    let callback = match registration.callback {
+        Some(ReportCallback::TimeStamped(ref mut callback)) => callback,
+        _ => return,
+    };
+    let report = report_slice(report, report_length);
+
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        callback(report_type, report_id, report, timestamp)
+    }));
+}
+
+unsafe fn report_slice<'a>(report: *mut u8, report_length: isize) -> &'a [u8] {
+    if report.is_null() || report_length <= 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(report as *const u8, report_length as usize)
+    }
+}
+
+impl TIOProperties for IOHIDDevice {
+    fn copy_cf_property(&self, key: &CFString) -> Option<CFType> {
+        unsafe {
+            let result = IOHIDDeviceGetProperty(self.device, key.as_concrete_TypeRef());
+
+            if result.is_null() {
+                None
+            } else {
+                Some(TCFType::wrap_under_get_rule(result))
+            }
+        }
+    }
+
+    fn set_cf_property(&self, key: &CFString, value: &CFType) -> Result<(), Error> {
+        unsafe {
+            if IOHIDDeviceSetProperty(self.device, key.as_concrete_TypeRef(), value.as_CFTypeRef())
+                != 0
+            {
+                Ok(())
+            } else {
+                Err(Error::new(kIOReturnError))
+            }
+        }
+    }
+}
+
+impl TCFType for IOHIDDevice {
+    type Ref = IOHIDDeviceRef;
+
+    fn as_concrete_TypeRef(&self) -> IOHIDDeviceRef {
+        self.device
+    }
+
+    unsafe fn wrap_under_get_rule(reference: IOHIDDeviceRef) -> IOHIDDevice {
+        assert!(!reference.is_null(), "Attempted to create a NULL object.");
+
+        let reference = CFRetain(reference as CFTypeRef) as IOHIDDeviceRef;
+
+        IOHIDDevice::wrap_under_create_rule(reference)
+    }
+
+    fn as_CFTypeRef(&self) -> CFTypeRef {
+        self.device as CFTypeRef
+    }
+
+    unsafe fn wrap_under_create_rule(reference: IOHIDDeviceRef) -> IOHIDDevice {
+        assert!(!reference.is_null(), "Attempted to create a NULL object.");
+
+        IOHIDDevice {
+            device: reference,
+            input_value_callback: None,
+            input_report_callback: None,
+        }
+    }
+
+    fn type_id() -> CFTypeID {
+        unsafe { IOHIDDeviceGetTypeID() }
+    }
+}
+
+unsafe impl ConcreteCFType for IOHIDDevice {}
+
+impl Clone for IOHIDDevice {
+    fn clone(&self) -> IOHIDDevice {
+        unsafe { IOHIDDevice::wrap_under_get_rule(self.device) }
+    }
+}
+
+impl PartialEq for IOHIDDevice {
+    fn eq(&self, other: &IOHIDDevice) -> bool {
+        self.as_CFType().eq(&other.as_CFType())
+    }
 }
 
-impl_TCFType!(IOHIDDevice, IOHIDDeviceRef, IOHIDDeviceGetTypeID);
+impl Eq for IOHIDDevice {}
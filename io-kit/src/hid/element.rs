@@ -1,7 +1,17 @@
+use std::collections::BTreeMap;
+
 use core_foundation::base::{CFRelease, TCFType};
+use core_foundation::dictionary::CFDictionary;
+use core_foundation::string::CFString;
 
 pub use io_kit_sys::hid::base::IOHIDElementRef;
 pub use io_kit_sys::hid::element::*;
+use io_kit_sys::hid::keys::{
+    kIOHIDElementCookieKey, kIOHIDElementTypeKey, kIOHIDElementUsageKey, kIOHIDElementUsageMaxKey,
+    kIOHIDElementUsageMinKey, kIOHIDElementUsagePageKey, IOHIDElementCookie, IOHIDElementType,
+};
+
+use crate::{hid::device::IOHIDDevice, matching::key, property::PropertyValue};
 
 pub struct IOHIDElement(IOHIDElementRef);
 
@@ -11,4 +21,160 @@ impl Drop for IOHIDElement {
     }
 }
 
+impl IOHIDElement {
+    /// The device the element belongs to.
+    pub fn device(&self) -> Option<IOHIDDevice> {
+        unsafe {
+            let device = IOHIDElementGetDevice(self.0);
+
+            if device.is_null() {
+                None
+            } else {
+                Some(IOHIDDevice::wrap_under_get_rule(device))
+            }
+        }
+    }
+
+    pub fn cookie(&self) -> IOHIDElementCookie {
+        unsafe { IOHIDElementGetCookie(self.0) }
+    }
+
+    /// One of the `kIOHIDElementType*` values.
+    pub fn element_type(&self) -> IOHIDElementType {
+        unsafe { IOHIDElementGetType(self.0) }
+    }
+
+    pub fn usage_page(&self) -> u32 {
+        unsafe { IOHIDElementGetUsagePage(self.0) }
+    }
+
+    pub fn usage(&self) -> u32 {
+        unsafe { IOHIDElementGetUsage(self.0) }
+    }
+
+    pub fn name(&self) -> Option<String> {
+        unsafe {
+            let name = IOHIDElementGetName(self.0);
+
+            if name.is_null() {
+                None
+            } else {
+                Some(CFString::wrap_under_get_rule(name).to_string())
+            }
+        }
+    }
+
+    pub fn report_id(&self) -> u32 {
+        unsafe { IOHIDElementGetReportID(self.0) }
+    }
+
+    pub fn logical_min(&self) -> isize {
+        unsafe { IOHIDElementGetLogicalMin(self.0) }
+    }
+
+    pub fn logical_max(&self) -> isize {
+        unsafe { IOHIDElementGetLogicalMax(self.0) }
+    }
+
+    pub fn physical_min(&self) -> isize {
+        unsafe { IOHIDElementGetPhysicalMin(self.0) }
+    }
+
+    pub fn physical_max(&self) -> isize {
+        unsafe { IOHIDElementGetPhysicalMax(self.0) }
+    }
+
+    /// Whether values are changes from the previous one, as for a mouse.
+    pub fn is_relative(&self) -> bool {
+        unsafe { IOHIDElementIsRelative(self.0) != 0 }
+    }
+
+    pub fn is_array(&self) -> bool {
+        unsafe { IOHIDElementIsArray(self.0) != 0 }
+    }
+}
+
 impl_TCFType!(IOHIDElement, IOHIDElementRef, IOHIDElementGetTypeID);
+
+/// Builds the input value matching dictionary of an `IOHIDDevice` or
+/// `IOHIDManager`. An element matches when it satisfies every criterion set.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct HidElementMatching {
+    usage_page: Option<u32>,
+    usage: Option<u32>,
+    usage_range: Option<(u32, u32)>,
+    cookie: Option<IOHIDElementCookie>,
+    element_type: Option<IOHIDElementType>,
+}
+
+impl HidElementMatching {
+    pub fn new() -> HidElementMatching {
+        HidElementMatching::default()
+    }
+
+    /// `UsagePage`
+    pub fn usage_page(mut self, usage_page: u32) -> HidElementMatching {
+        self.usage_page = Some(usage_page);
+        self
+    }
+
+    /// `Usage`, within the usage page.
+    pub fn usage(mut self, usage: u32) -> HidElementMatching {
+        self.usage = Some(usage);
+        self
+    }
+
+    /// `UsageMin` and `UsageMax`, both inclusive.
+    pub fn usage_range(mut self, min: u32, max: u32) -> HidElementMatching {
+        self.usage_range = Some((min, max));
+        self
+    }
+
+    /// `ElementCookie`
+    pub fn cookie(mut self, cookie: IOHIDElementCookie) -> HidElementMatching {
+        self.cookie = Some(cookie);
+        self
+    }
+
+    /// `Type`, one of the `kIOHIDElementType*` values.
+    pub fn element_type(mut self, element_type: IOHIDElementType) -> HidElementMatching {
+        self.element_type = Some(element_type);
+        self
+    }
+
+    /// Returns the matching dictionary as a property list value.
+    pub fn to_property_value(&self) -> PropertyValue {
+        let mut map = BTreeMap::new();
+
+        if let Some(usage_page) = self.usage_page {
+            map.insert(key(kIOHIDElementUsagePageKey), usage_page.into());
+        }
+        if let Some(usage) = self.usage {
+            map.insert(key(kIOHIDElementUsageKey), usage.into());
+        }
+        if let Some((min, max)) = self.usage_range {
+            map.insert(key(kIOHIDElementUsageMinKey), min.into());
+            map.insert(key(kIOHIDElementUsageMaxKey), max.into());
+        }
+        if let Some(cookie) = self.cookie {
+            map.insert(key(kIOHIDElementCookieKey), cookie.into());
+        }
+        if let Some(element_type) = self.element_type {
+            map.insert(key(kIOHIDElementTypeKey), element_type.into());
+        }
+
+        PropertyValue::Dictionary(map)
+    }
+
+    pub fn to_cf_dictionary(&self) -> CFDictionary {
+        let dictionary = self.to_property_value().to_cf_type();
+
+        unsafe { CFDictionary::wrap_under_get_rule(dictionary.as_CFTypeRef() as _) }
+    }
+}
+
+impl From<HidElementMatching> for CFDictionary {
+    fn from(matching: HidElementMatching) -> CFDictionary {
+        matching.to_cf_dictionary()
+    }
+}
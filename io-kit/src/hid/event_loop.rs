@@ -0,0 +1,290 @@
+//! HID events delivered over a channel from a `CFRunLoop` on a thread of its
+//! own, for programs that do not run a run loop themselves.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use core_foundation::runloop::{kCFRunLoopDefaultMode, CFRunLoop};
+use io_kit_sys::hid::keys::{IOHIDElementCookie, IOHIDReportType};
+
+use crate::{
+    base::IOService,
+    error::Error,
+    hid::{
+        device::IOHIDDevice,
+        element::{HidElementMatching, IOHIDElement},
+        manager::{kIOHIDManagerOptionNone, HidDeviceMatching, IOHIDManager},
+        value::IOHIDValue,
+    },
+    ret::kIOReturnError,
+    runloop::BackgroundRunLoop,
+};
+
+/// Devices are given by their registry entries, and values and reports by
+/// copies of their contents, all of which can be sent across threads.
+pub enum HidEvent {
+    /// A device matched by a manager, including the ones present when the
+    /// manager was added.
+    Attached(IOService),
+    /// A device matched by a manager was removed.
+    Detached(IOService),
+    /// An input value of an element, with its `mach_absolute_time`
+    /// timestamp.
+    Value {
+        device: IOService,
+        usage_page: u32,
+        usage: u32,
+        cookie: IOHIDElementCookie,
+        integer_value: isize,
+        bytes: Vec<u8>,
+        timestamp: u64,
+    },
+    /// An input report, with its `mach_absolute_time` timestamp.
+    Report {
+        device: IOService,
+        report_type: IOHIDReportType,
+        report_id: u32,
+        report: Vec<u8>,
+        timestamp: u64,
+    },
+}
+
+impl HidEvent {
+    fn value(device: IOService, value: &IOHIDValue, element: &IOHIDElement) -> HidEvent {
+        HidEvent::Value {
+            device,
+            usage_page: element.usage_page(),
+            usage: element.usage(),
+            cookie: element.cookie(),
+            integer_value: value.integer_value(),
+            bytes: value.bytes().to_vec(),
+            timestamp: value.timestamp(),
+        }
+    }
+}
+
+// every event loop runs on a thread of its own, which holds its state
+thread_local! {
+    static LOOP_STATE: RefCell<LoopState> = const {
+        RefCell::new(LoopState {
+            managers: Vec::new(),
+            devices: Vec::new(),
+        })
+    };
+}
+
+struct LoopState {
+    managers: Vec<IOHIDManager>,
+    devices: Vec<IOHIDDevice>,
+}
+
+/// Schedules HID managers and devices on a run loop thread of its own and
+/// forwards their events to the receiver returned by `new`. Dropping the
+/// loop unschedules and closes everything, then stops the thread, after
+/// which the receiver is disconnected.
+pub struct HidEventLoop {
+    run_loop: BackgroundRunLoop,
+    sender: Sender<HidEvent>,
+}
+
+impl HidEventLoop {
+    pub fn new() -> (HidEventLoop, Receiver<HidEvent>) {
+        let (sender, receiver) = mpsc::channel();
+        let event_loop = HidEventLoop {
+            run_loop: BackgroundRunLoop::spawn("io-kit-hid-event-loop"),
+            sender,
+        };
+
+        (event_loop, receiver)
+    }
+
+    /// Adds a manager for the devices matching any of `devices`, reporting
+    /// the values of the elements matching any of `values`. Empty slices
+    /// match everything. Input reports are forwarded from every device that
+    /// has them.
+    pub fn add_manager(
+        &self,
+        devices: &[HidDeviceMatching],
+        values: &[HidElementMatching],
+    ) -> Result<(), Error> {
+        let devices = devices.to_vec();
+        let values = values.to_vec();
+        let sender = self.sender.clone();
+
+        self.run_loop.run(move || {
+            let mut manager = IOHIDManager::new().ok_or(Error::new(kIOReturnError))?;
+            let run_loop = CFRunLoop::get_current();
+            let mode = unsafe { kCFRunLoopDefaultMode };
+
+            // the attached devices own their report callbacks
+            let attached = Rc::new(RefCell::new(Vec::<IOHIDDevice>::new()));
+            let detached = attached.clone();
+            let detached_run_loop = run_loop.clone();
+            let attached_sender = sender.clone();
+            let detached_sender = sender.clone();
+
+            manager.set_device_matching_multiple(&devices);
+            manager.set_input_value_matching_multiple(&values);
+            manager.register_device_matching_callback(move |mut device| {
+                if let Some(service) = device.get_service() {
+                    // devices are closed before they are dropped, when they
+                    // are removed or else with the manager
+                    unsafe {
+                        forward_reports(&mut device, service.clone(), attached_sender.clone())
+                    };
+                    let _ = attached_sender.send(HidEvent::Attached(service));
+                    attached.borrow_mut().push(device);
+                }
+            });
+            manager.register_device_removal_callback(move |device| {
+                let index = detached
+                    .borrow()
+                    .iter()
+                    .position(|attached| *attached == device);
+
+                // no more reports may be written into its buffer once it is
+                // dropped
+                if let Some(index) = index {
+                    let removed = detached.borrow_mut().swap_remove(index);
+                    removed.unschedule_from_run_loop(&detached_run_loop, mode);
+                    let _ = removed.close();
+                }
+
+                if let Some(service) = device.get_service() {
+                    let _ = detached_sender.send(HidEvent::Detached(service));
+                }
+            });
+            manager.register_input_value_callback(move |value, element, device| {
+                if let Some(device) = device.get_service() {
+                    let _ = sender.send(HidEvent::value(device, &value, &element));
+                }
+            });
+            manager.schedule_with_run_loop(&run_loop, mode);
+
+            if let Err(error) = manager.open(kIOHIDManagerOptionNone) {
+                manager.unschedule_from_run_loop(&run_loop, mode);
+                return Err(error);
+            }
+
+            LOOP_STATE.with(|state| state.borrow_mut().managers.push(manager));
+
+            Ok(())
+        })
+    }
+
+    /// Opens the HID device of `service` and forwards its input values,
+    /// limited to the elements matching any of `values`, and its input
+    /// reports.
+    pub fn add_device(
+        &self,
+        service: IOService,
+        values: &[HidElementMatching],
+    ) -> Result<(), Error> {
+        let values = values.to_vec();
+        let sender = self.sender.clone();
+
+        self.run_loop.run(move || {
+            let run_loop = CFRunLoop::get_current();
+            let mode = unsafe { kCFRunLoopDefaultMode };
+            let mut device =
+                IOHIDDevice::create(service.clone()).ok_or(Error::new(kIOReturnError))?;
+
+            device.set_input_value_matching_multiple(&values);
+            // the device is closed before it is dropped, here or on shutdown
+            unsafe { forward_reports(&mut device, service.clone(), sender.clone()) };
+            device.register_input_value_callback(move |value, element, _| {
+                let _ = sender.send(HidEvent::value(service.clone(), &value, &element));
+            });
+            device.schedule_with_run_loop(&run_loop, mode);
+
+            if let Err(error) = device.open() {
+                device.unschedule_from_run_loop(&run_loop, mode);
+                return Err(error);
+            }
+
+            LOOP_STATE.with(|state| state.borrow_mut().devices.push(device));
+
+            Ok(())
+        })
+    }
+
+    /// Unschedules and closes everything and stops the thread, like dropping
+    /// the loop.
+    pub fn shutdown(self) {
+        drop(self)
+    }
+}
+
+impl Drop for HidEventLoop {
+    fn drop(&mut self) {
+        // there is nothing left to close if the thread is already gone
+        let _ = self.run_loop.try_run(|| {
+            let run_loop = CFRunLoop::get_current();
+            let mode = unsafe { kCFRunLoopDefaultMode };
+            let state = LOOP_STATE.with(|state| {
+                let mut state = state.borrow_mut();
+
+                LoopState {
+                    managers: state.managers.drain(..).collect(),
+                    devices: state.devices.drain(..).collect(),
+                }
+            });
+
+            // dropping them unregisters their callbacks, and with them the
+            // senders they hold
+            for manager in state.managers {
+                manager.unschedule_from_run_loop(&run_loop, mode);
+                let _ = manager.close(kIOHIDManagerOptionNone);
+            }
+            for device in state.devices {
+                device.unschedule_from_run_loop(&run_loop, mode);
+                let _ = device.close();
+            }
+        });
+
+        self.run_loop.stop();
+    }
+}
+
+// devices without input reports only send values; the caller must close or
+// unschedule the device before dropping it, as for the registration
+unsafe fn forward_reports(device: &mut IOHIDDevice, service: IOService, sender: Sender<HidEvent>) {
+    let _ = device.register_input_report_with_timestamp_callback(
+        move |report_type, report_id, report, timestamp| {
+            let _ = sender.send(HidEvent::Report {
+                device: service.clone(),
+                report_type,
+                report_id,
+                report: report.to_vec(),
+                timestamp,
+            });
+        },
+    );
+}
+
+#[cfg(test)]
+#[cfg(target_os = "macos")]
+mod tests {
+    use std::sync::mpsc::RecvTimeoutError;
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn receiver_disconnects_after_shutdown() {
+        let (event_loop, receiver) = HidEventLoop::new();
+
+        // matches no device, so there is nothing to be allowed to open
+        let nothing = HidDeviceMatching::new()
+            .vendor_id(0xffff)
+            .product_id(0xffff);
+        event_loop.add_manager(&[nothing], &[]).unwrap();
+        event_loop.shutdown();
+
+        assert!(matches!(
+            receiver.recv_timeout(Duration::from_secs(5)),
+            Err(RecvTimeoutError::Disconnected)
+        ));
+    }
+}
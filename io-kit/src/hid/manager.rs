@@ -1,25 +1,494 @@
-use core_foundation::base::{kCFAllocatorDefault, CFRelease, TCFType};
+use std::collections::BTreeMap;
+use std::os::raw::c_void;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
 
+use core_foundation::array::CFArray;
+use core_foundation::base::{
+    kCFAllocatorDefault, CFRelease, CFRetain, CFType, CFTypeID, CFTypeRef, TCFType,
+};
+use core_foundation::dictionary::CFDictionary;
+use core_foundation::runloop::CFRunLoop;
+use core_foundation::set::{CFSet, CFSetGetCount, CFSetGetValues};
+use core_foundation::string::{CFString, CFStringRef};
+use core_foundation::ConcreteCFType;
+
+use io_kit_sys::hid::base::IOHIDDeviceRef;
 pub use io_kit_sys::hid::manager::*;
 
-pub struct IOHIDManager(IOHIDManagerRef);
+use crate::{
+    error::Error,
+    hid::{
+        callbacks::{self, Slot},
+        device::IOHIDDevice,
+        element::{HidElementMatching, IOHIDElement},
+        value::{input_value_callback, no_input_value_callback, IOHIDValue},
+    },
+    keys::typed::{
+        kIOHIDDeviceUsageKey, kIOHIDDeviceUsagePageKey, kIOHIDProductIDKey, kIOHIDTransportKey,
+        kIOHIDVendorIDKey,
+    },
+    property::{PropertyValue, TIOProperties},
+    ret::{kIOReturnError, kIOReturnSuccess, IOReturn},
+};
+
+type DeviceCallback = Box<dyn FnMut(IOHIDDevice)>;
+type InputValueCallback = dyn FnMut(IOHIDValue, IOHIDElement, IOHIDDevice);
+
+/// A HID manager. The callbacks registered through it are owned by this value
+/// and unregistered when it is dropped, unless another value for the same
+/// manager has since replaced them. They run on the run loop the manager is
+/// scheduled on, which `HidEventLoop` and `HidDeviceStream` do on threads of
+/// their own, so the manager must be unscheduled, or dropped, on that run
+/// loop's thread.
+pub struct IOHIDManager {
+    manager: IOHIDManagerRef,
+    device_matching_callback: Option<Box<DeviceCallback>>,
+    device_removal_callback: Option<Box<DeviceCallback>>,
+    input_value_callback: Option<Box<Box<InputValueCallback>>>,
+}
 
 impl Drop for IOHIDManager {
     fn drop(&mut self) {
+        // other references may keep the manager alive past the callbacks
+        self.unregister_device_matching_callback();
+        self.unregister_device_removal_callback();
+        self.unregister_input_value_callback();
+
         unsafe { CFRelease(self.as_CFTypeRef()) }
     }
 }
 
 impl IOHIDManager {
     pub fn new() -> Option<Self> {
-        let m = unsafe { IOHIDManagerCreate(kCFAllocatorDefault, kIOHIDManagerOptionNone) };
+        IOHIDManager::with_options(kIOHIDManagerOptionNone)
+    }
+
+    /// Creates a manager with `kIOHIDManagerOption*` flags, such as
+    /// `kIOHIDManagerOptionUsePersistentProperties`.
+    pub fn with_options(options: IOHIDManagerOptions) -> Option<Self> {
+        let m = unsafe { IOHIDManagerCreate(kCFAllocatorDefault, options) };
 
         if m.is_null() {
             None
         } else {
-            Some(IOHIDManager(m))
+            Some(unsafe { IOHIDManager::wrap_under_create_rule(m) })
+        }
+    }
+
+    /// Opens the matched devices, and those matched later on.
+    pub fn open(&self, options: IOHIDManagerOptions) -> Result<(), Error> {
+        unsafe {
+            let result = IOHIDManagerOpen(self.manager, options);
+
+            if result == kIOReturnSuccess {
+                Ok(())
+            } else {
+                Err(Error::new(result))
+            }
+        }
+    }
+
+    pub fn close(&self, options: IOHIDManagerOptions) -> Result<(), Error> {
+        unsafe {
+            let result = IOHIDManagerClose(self.manager, options);
+
+            if result == kIOReturnSuccess {
+                Ok(())
+            } else {
+                Err(Error::new(result))
+            }
+        }
+    }
+
+    /// Delivers the callbacks on `run_loop` while it runs in `mode`.
+    pub(crate) fn schedule_with_run_loop(&self, run_loop: &CFRunLoop, mode: CFStringRef) {
+        unsafe {
+            IOHIDManagerScheduleWithRunLoop(self.manager, run_loop.as_concrete_TypeRef(), mode)
+        }
+    }
+
+    pub(crate) fn unschedule_from_run_loop(&self, run_loop: &CFRunLoop, mode: CFStringRef) {
+        unsafe {
+            IOHIDManagerUnscheduleFromRunLoop(self.manager, run_loop.as_concrete_TypeRef(), mode)
+        }
+    }
+
+    /// Replaces the devices the manager looks after. Matching without any
+    /// criterion set matches every device.
+    pub fn set_device_matching(&self, matching: &HidDeviceMatching) {
+        let matching = matching.to_cf_dictionary();
+
+        unsafe { IOHIDManagerSetDeviceMatching(self.manager, matching.as_concrete_TypeRef()) }
+    }
+
+    /// Like `set_device_matching`, but a device may match any of `matchings`.
+    /// An empty slice matches every device.
+    pub fn set_device_matching_multiple(&self, matchings: &[HidDeviceMatching]) {
+        if matchings.is_empty() {
+            unsafe { IOHIDManagerSetDeviceMatchingMultiple(self.manager, ptr::null()) };
+            return;
+        }
+
+        let matchings = matchings
+            .iter()
+            .map(HidDeviceMatching::to_cf_dictionary)
+            .collect::<Vec<_>>();
+        let array = CFArray::from_CFTypes(&matchings);
+
+        unsafe { IOHIDManagerSetDeviceMatchingMultiple(self.manager, array.as_concrete_TypeRef()) }
+    }
+
+    /// Returns the devices currently matched.
+    pub fn devices(&self) -> Vec<IOHIDDevice> {
+        unsafe {
+            let set = IOHIDManagerCopyDevices(self.manager);
+
+            if set.is_null() {
+                return Vec::new();
+            }
+
+            let set: CFSet = CFSet::wrap_under_create_rule(set);
+            let count = CFSetGetCount(set.as_concrete_TypeRef()) as usize;
+            let mut values: Vec<*const c_void> = vec![ptr::null(); count];
+
+            CFSetGetValues(set.as_concrete_TypeRef(), values.as_mut_ptr());
+
+            values
+                .into_iter()
+                .map(|device| IOHIDDevice::wrap_under_get_rule(device as _))
+                .collect()
+        }
+    }
+
+    /// Calls `callback` with each device matched, starting with the ones
+    /// already present once the manager is scheduled on a run loop. Replaces
+    /// any previous callback.
+    pub fn register_device_matching_callback<F>(&mut self, callback: F)
+    where
+        F: FnMut(IOHIDDevice) + 'static,
+    {
+        let mut callback: Box<DeviceCallback> = Box::new(Box::new(callback));
+        let context = &mut *callback as *mut DeviceCallback as *mut c_void;
+
+        callbacks::register(
+            self.as_CFTypeRef(),
+            Slot::DeviceMatching,
+            context,
+            || unsafe {
+                IOHIDManagerRegisterDeviceMatchingCallback(self.manager, device_callback, context)
+            },
+        );
+
+        self.device_matching_callback = Some(callback);
+    }
+
+    pub fn unregister_device_matching_callback(&mut self) {
+        if let Some(mut callback) = self.device_matching_callback.take() {
+            let context = &mut *callback as *mut DeviceCallback as *mut c_void;
+
+            callbacks::unregister(
+                self.as_CFTypeRef(),
+                Slot::DeviceMatching,
+                context,
+                || unsafe {
+                    IOHIDManagerRegisterDeviceMatchingCallback(
+                        self.manager,
+                        no_device_callback,
+                        ptr::null_mut(),
+                    )
+                },
+            );
+        }
+    }
+
+    /// Calls `callback` with each matched device that is removed. Replaces
+    /// any previous callback.
+    pub fn register_device_removal_callback<F>(&mut self, callback: F)
+    where
+        F: FnMut(IOHIDDevice) + 'static,
+    {
+        let mut callback: Box<DeviceCallback> = Box::new(Box::new(callback));
+        let context = &mut *callback as *mut DeviceCallback as *mut c_void;
+
+        callbacks::register(
+            self.as_CFTypeRef(),
+            Slot::DeviceRemoval,
+            context,
+            || unsafe {
+                IOHIDManagerRegisterDeviceRemovalCallback(self.manager, device_callback, context)
+            },
+        );
+
+        self.device_removal_callback = Some(callback);
+    }
+
+    pub fn unregister_device_removal_callback(&mut self) {
+        if let Some(mut callback) = self.device_removal_callback.take() {
+            let context = &mut *callback as *mut DeviceCallback as *mut c_void;
+
+            callbacks::unregister(
+                self.as_CFTypeRef(),
+                Slot::DeviceRemoval,
+                context,
+                || unsafe {
+                    IOHIDManagerRegisterDeviceRemovalCallback(
+                        self.manager,
+                        no_device_callback,
+                        ptr::null_mut(),
+                    )
+                },
+            );
+        }
+    }
+
+    /// Calls `callback` with each input value reported by the opened
+    /// devices. Replaces any previous callback.
+    pub fn register_input_value_callback<F>(&mut self, callback: F)
+    where
+        F: FnMut(IOHIDValue, IOHIDElement, IOHIDDevice) + 'static,
+    {
+        let mut callback: Box<Box<InputValueCallback>> = Box::new(Box::new(callback));
+        let context = &mut *callback as *mut Box<InputValueCallback> as *mut c_void;
+
+        callbacks::register(self.as_CFTypeRef(), Slot::InputValue, context, || unsafe {
+            IOHIDManagerRegisterInputValueCallback(
+                self.manager,
+                input_value_callback::<InputValueCallback>,
+                context,
+            )
+        });
+
+        self.input_value_callback = Some(callback);
+    }
+
+    pub fn unregister_input_value_callback(&mut self) {
+        if let Some(mut callback) = self.input_value_callback.take() {
+            let context = &mut *callback as *mut Box<InputValueCallback> as *mut c_void;
+
+            callbacks::unregister(self.as_CFTypeRef(), Slot::InputValue, context, || unsafe {
+                IOHIDManagerRegisterInputValueCallback(
+                    self.manager,
+                    no_input_value_callback,
+                    ptr::null_mut(),
+                )
+            });
+        }
+    }
+
+    /// Limits the input value callback to the elements matching `matching`.
+    pub fn set_input_value_matching(&self, matching: &HidElementMatching) {
+        let matching = matching.to_cf_dictionary();
+
+        unsafe { IOHIDManagerSetInputValueMatching(self.manager, matching.as_concrete_TypeRef()) }
+    }
+
+    /// Like `set_input_value_matching`, but an element may match any of
+    /// `matchings`. An empty slice matches every element.
+    pub fn set_input_value_matching_multiple(&self, matchings: &[HidElementMatching]) {
+        if matchings.is_empty() {
+            unsafe { IOHIDManagerSetInputValueMatchingMultiple(self.manager, ptr::null()) };
+            return;
+        }
+
+        let matchings = matchings
+            .iter()
+            .map(HidElementMatching::to_cf_dictionary)
+            .collect::<Vec<_>>();
+        let array = CFArray::from_CFTypes(&matchings);
+
+        unsafe {
+            IOHIDManagerSetInputValueMatchingMultiple(self.manager, array.as_concrete_TypeRef())
+        }
+    }
+}
+
+unsafe extern "C" fn device_callback(
+    context: *mut c_void,
+    _result: IOReturn,
+    _sender: *mut c_void,
+    device: IOHIDDeviceRef,
+) {
+    if device.is_null() {
+        return;
+    }
+
+    let callback = &mut *(context as *mut DeviceCallback);
+    let device = IOHIDDevice::wrap_under_get_rule(device);
+
+    // the panic hook has already reported the panic; it must not unwind
+    // into IOKit
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| callback(device)));
+}
+
+// stands in for the null callback, which the bindings cannot express
+unsafe extern "C" fn no_device_callback(
+    _context: *mut c_void,
+    _result: IOReturn,
+    _sender: *mut c_void,
+    _device: IOHIDDeviceRef,
+) {
+}
+
+impl TIOProperties for IOHIDManager {
+    fn copy_cf_property(&self, key: &CFString) -> Option<CFType> {
+        unsafe {
+            let result = IOHIDManagerGetProperty(self.manager, key.as_concrete_TypeRef());
+
+            if result.is_null() {
+                None
+            } else {
+                Some(TCFType::wrap_under_get_rule(result))
+            }
+        }
+    }
+
+    fn set_cf_property(&self, key: &CFString, value: &CFType) -> Result<(), Error> {
+        unsafe {
+            if IOHIDManagerSetProperty(
+                self.manager,
+                key.as_concrete_TypeRef(),
+                value.as_CFTypeRef(),
+            ) != 0
+            {
+                Ok(())
+            } else {
+                Err(Error::new(kIOReturnError))
+            }
         }
     }
 }
 
-impl_TCFType!(IOHIDManager, IOHIDManagerRef, IOHIDManagerGetTypeID);
+impl TCFType for IOHIDManager {
+    type Ref = IOHIDManagerRef;
+
+    fn as_concrete_TypeRef(&self) -> IOHIDManagerRef {
+        self.manager
+    }
+
+    unsafe fn wrap_under_get_rule(reference: IOHIDManagerRef) -> IOHIDManager {
+        assert!(!reference.is_null(), "Attempted to create a NULL object.");
+
+        let reference = CFRetain(reference as CFTypeRef) as IOHIDManagerRef;
+
+        IOHIDManager::wrap_under_create_rule(reference)
+    }
+
+    fn as_CFTypeRef(&self) -> CFTypeRef {
+        self.manager as CFTypeRef
+    }
+
+    unsafe fn wrap_under_create_rule(reference: IOHIDManagerRef) -> IOHIDManager {
+        assert!(!reference.is_null(), "Attempted to create a NULL object.");
+
+        IOHIDManager {
+            manager: reference,
+            device_matching_callback: None,
+            device_removal_callback: None,
+            input_value_callback: None,
+        }
+    }
+
+    fn type_id() -> CFTypeID {
+        unsafe { IOHIDManagerGetTypeID() }
+    }
+}
+
+unsafe impl ConcreteCFType for IOHIDManager {}
+
+impl PartialEq for IOHIDManager {
+    fn eq(&self, other: &IOHIDManager) -> bool {
+        self.as_CFType().eq(&other.as_CFType())
+    }
+}
+
+impl Eq for IOHIDManager {}
+
+/// Builds the device matching dictionary of an `IOHIDManager`. A device
+/// matches when it satisfies every criterion set.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct HidDeviceMatching {
+    vendor_id: Option<u32>,
+    product_id: Option<u32>,
+    usage_page: Option<u32>,
+    usage: Option<u32>,
+    transport: Option<String>,
+}
+
+impl HidDeviceMatching {
+    pub fn new() -> HidDeviceMatching {
+        HidDeviceMatching::default()
+    }
+
+    /// `VendorID`
+    pub fn vendor_id(mut self, vendor_id: u32) -> HidDeviceMatching {
+        self.vendor_id = Some(vendor_id);
+        self
+    }
+
+    /// `ProductID`
+    pub fn product_id(mut self, product_id: u32) -> HidDeviceMatching {
+        self.product_id = Some(product_id);
+        self
+    }
+
+    /// `DeviceUsagePage`: any of the usage pairs of the device may match, not
+    /// only the primary one.
+    pub fn usage_page(mut self, usage_page: u32) -> HidDeviceMatching {
+        self.usage_page = Some(usage_page);
+        self
+    }
+
+    /// `DeviceUsage`, within the usage page.
+    pub fn usage(mut self, usage: u32) -> HidDeviceMatching {
+        self.usage = Some(usage);
+        self
+    }
+
+    /// `Transport`, such as `"USB"` or `"Bluetooth"`.
+    pub fn transport(mut self, transport: &str) -> HidDeviceMatching {
+        self.transport = Some(transport.to_string());
+        self
+    }
+
+    /// Returns the matching dictionary as a property list value.
+    pub fn to_property_value(&self) -> PropertyValue {
+        let mut map = BTreeMap::new();
+
+        if let Some(vendor_id) = self.vendor_id {
+            map.insert(kIOHIDVendorIDKey.name().to_string(), vendor_id.into());
+        }
+        if let Some(product_id) = self.product_id {
+            map.insert(kIOHIDProductIDKey.name().to_string(), product_id.into());
+        }
+        if let Some(usage_page) = self.usage_page {
+            map.insert(
+                kIOHIDDeviceUsagePageKey.name().to_string(),
+                usage_page.into(),
+            );
+        }
+        if let Some(usage) = self.usage {
+            map.insert(kIOHIDDeviceUsageKey.name().to_string(), usage.into());
+        }
+        if let Some(ref transport) = self.transport {
+            map.insert(
+                kIOHIDTransportKey.name().to_string(),
+                transport.clone().into(),
+            );
+        }
+
+        PropertyValue::Dictionary(map)
+    }
+
+    pub fn to_cf_dictionary(&self) -> CFDictionary {
+        let dictionary = self.to_property_value().to_cf_type();
+
+        unsafe { CFDictionary::wrap_under_get_rule(dictionary.as_CFTypeRef() as _) }
+    }
+}
+
+impl From<HidDeviceMatching> for CFDictionary {
+    fn from(matching: HidDeviceMatching) -> CFDictionary {
+        matching.to_cf_dictionary()
+    }
+}
@@ -1,7 +1,9 @@
 pub use io_kit_sys::hid::keys;
 pub use io_kit_sys::hid::usage_tables;
 
+mod callbacks;
 pub mod device;
 pub mod element;
+pub mod event_loop;
 pub mod manager;
 pub mod value;
@@ -1,8 +1,18 @@
+use std::os::raw::c_void;
+use std::panic::{self, AssertUnwindSafe};
+use std::slice;
+
 use core_foundation::base::{CFRelease, TCFType};
 
 pub use io_kit_sys::hid::base::IOHIDValueRef;
+use io_kit_sys::hid::keys::IOHIDValueScaleType;
 pub use io_kit_sys::hid::value::*;
 
+use crate::{
+    hid::{device::IOHIDDevice, element::IOHIDElement},
+    ret::IOReturn,
+};
+
 pub struct IOHIDValue(IOHIDValueRef);
 
 impl Drop for IOHIDValue {
@@ -11,4 +21,85 @@ impl Drop for IOHIDValue {
     }
 }
 
+impl IOHIDValue {
+    pub fn element(&self) -> Option<IOHIDElement> {
+        unsafe {
+            let element = IOHIDValueGetElement(self.0);
+
+            if element.is_null() {
+                None
+            } else {
+                Some(IOHIDElement::wrap_under_get_rule(element))
+            }
+        }
+    }
+
+    /// The time the value was received, in `mach_absolute_time` units.
+    pub fn timestamp(&self) -> u64 {
+        unsafe { IOHIDValueGetTimeStamp(self.0) }
+    }
+
+    pub fn integer_value(&self) -> isize {
+        unsafe { IOHIDValueGetIntegerValue(self.0) }
+    }
+
+    /// The value scaled by `kIOHIDValueScaleTypeCalibrated` or
+    /// `kIOHIDValueScaleTypePhysical`.
+    pub fn scaled_value(&self, scale_type: IOHIDValueScaleType) -> f64 {
+        unsafe { IOHIDValueGetScaledValue(self.0, scale_type) }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        unsafe {
+            let length = IOHIDValueGetLength(self.0) as usize;
+            let bytes = IOHIDValueGetBytePtr(self.0);
+
+            if bytes.is_null() || length == 0 {
+                &[]
+            } else {
+                slice::from_raw_parts(bytes, length)
+            }
+        }
+    }
+}
+
 impl_TCFType!(IOHIDValue, IOHIDValueRef, IOHIDValueGetTypeID);
+
+// the context is a `*mut Box<C>` owned by the device or manager that
+// registered it
+pub(crate) unsafe extern "C" fn input_value_callback<C>(
+    context: *mut c_void,
+    _result: IOReturn,
+    _sender: *mut c_void,
+    value: IOHIDValueRef,
+) where
+    C: ?Sized + FnMut(IOHIDValue, IOHIDElement, IOHIDDevice),
+{
+    if value.is_null() {
+        return;
+    }
+
+    let callback = &mut *(context as *mut Box<C>);
+    let value = IOHIDValue::wrap_under_get_rule(value);
+    let element = match value.element() {
+        Some(element) => element,
+        None => return,
+    };
+    let device = match element.device() {
+        Some(device) => device,
+        None => return,
+    };
+
+    // the panic hook has already reported the panic; it must not unwind
+    // into IOKit
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| callback(value, element, device)));
+}
+
+// stands in for the null callback, which the bindings cannot express
+pub(crate) unsafe extern "C" fn no_input_value_callback(
+    _context: *mut c_void,
+    _result: IOReturn,
+    _sender: *mut c_void,
+    _value: IOHIDValueRef,
+) {
+}
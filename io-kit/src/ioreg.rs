@@ -0,0 +1,353 @@
+//! Renders registry trees in the layout printed by `ioreg`.
+//!
+//! Live entries are captured into a `SnapshotEntry` first, so the output for
+//! a live registry and for a saved snapshot of it is the same.
+
+use std::fmt::Write;
+
+#[cfg(target_os = "macos")]
+use crate::base::IORegistryEntry;
+#[cfg(target_os = "macos")]
+use crate::error::Error;
+use crate::plane::IORegistryPlane;
+use crate::property::PropertyValue;
+use crate::snapshot::{RegistrySnapshot, SnapshotEntry};
+
+// IOService state bits, as reported in `IOServiceState`
+const kIOServiceInactiveState: u64 = 0x00000001;
+const kIOServiceRegisteredState: u64 = 0x00000002;
+const kIOServiceMatchedState: u64 = 0x00000004;
+
+#[derive(Clone, Debug)]
+pub struct IORegFormatter {
+    plane: IORegistryPlane,
+    properties: bool,
+    depth_limit: Option<usize>,
+}
+
+impl Default for IORegFormatter {
+    fn default() -> IORegFormatter {
+        IORegFormatter {
+            plane: IORegistryPlane::Service,
+            properties: false,
+            depth_limit: None,
+        }
+    }
+}
+
+impl IORegFormatter {
+    pub fn new() -> IORegFormatter {
+        IORegFormatter::default()
+    }
+
+    /// Selects the plane to render, like `ioreg -p`. Defaults to the service plane.
+    pub fn plane(mut self, plane: IORegistryPlane) -> IORegFormatter {
+        self.plane = plane;
+        self
+    }
+
+    /// Includes the property block of every entry, like `ioreg -l`.
+    pub fn properties(mut self, properties: bool) -> IORegFormatter {
+        self.properties = properties;
+        self
+    }
+
+    /// Limits the number of levels shown, like `ioreg -d`; a limit of 1 shows
+    /// only the top entry.
+    pub fn depth_limit(mut self, depth_limit: Option<usize>) -> IORegFormatter {
+        self.depth_limit = depth_limit;
+        self
+    }
+
+    /// Renders the tree held for the selected plane, if the snapshot has one.
+    pub fn format_snapshot(&self, snapshot: &RegistrySnapshot) -> Option<String> {
+        snapshot
+            .root(self.plane)
+            .map(|root| self.format_entry(root))
+    }
+
+    /// Renders `entry` and its children as the top of the tree.
+    pub fn format_entry(&self, entry: &SnapshotEntry) -> String {
+        let mut out = String::new();
+        self.write_entry(&mut out, entry, 0, &mut vec![false]);
+        out
+    }
+
+    /// Captures `entry` and everything below it in the selected plane, then
+    /// renders it.
+    #[cfg(target_os = "macos")]
+    pub fn format_live(&self, entry: &IORegistryEntry) -> Result<String, Error> {
+        SnapshotEntry::capture_tree(entry, self.plane).map(|entry| self.format_entry(&entry))
+    }
+
+    /// `siblings[i]` records whether the ancestor at depth `i` (or the entry
+    /// itself, for the last element) has more siblings below it.
+    fn write_entry(
+        &self,
+        out: &mut String,
+        entry: &SnapshotEntry,
+        depth: usize,
+        siblings: &mut Vec<bool>,
+    ) {
+        let show_children = self
+            .depth_limit
+            .filter(|&limit| depth + 1 >= limit)
+            .is_none()
+            && !entry.children.is_empty();
+
+        indent(out, &siblings[..siblings.len().saturating_sub(1)]);
+        out.push_str("+-o ");
+        write_label(out, entry);
+        out.push('\n');
+
+        if self.properties {
+            let mut prefix = String::new();
+            indent(&mut prefix, siblings);
+            prefix.push_str(if show_children { "| " } else { "  " });
+
+            let _ = writeln!(out, "{}{{", prefix);
+
+            for (key, value) in &entry.properties {
+                let _ = write!(out, "{}  \"{}\" = ", prefix, key);
+                write_value(out, value);
+                out.push('\n');
+            }
+
+            let _ = writeln!(out, "{}}}", prefix);
+            let _ = writeln!(out, "{}", prefix);
+        }
+
+        if show_children {
+            let count = entry.children.len();
+
+            for (index, child) in entry.children.iter().enumerate() {
+                siblings.push(index + 1 < count);
+                self.write_entry(out, child, depth + 1, siblings);
+                siblings.pop();
+            }
+        }
+    }
+}
+
+fn indent(out: &mut String, siblings: &[bool]) {
+    for &more in siblings {
+        out.push_str(if more { "| " } else { "  " });
+    }
+}
+
+fn write_label(out: &mut String, entry: &SnapshotEntry) {
+    out.push_str(&entry.name);
+
+    if let Some(ref location) = entry.location {
+        let _ = write!(out, "@{}", location);
+    }
+
+    let _ = write!(out, "  <class {}, id 0x{:x}", entry.class, entry.id);
+
+    if let Some(state) = entry.state {
+        let flag = |set: bool| if set { "" } else { "!" };
+
+        let _ = write!(
+            out,
+            ", {}registered, {}matched, {}active",
+            flag(state & kIOServiceRegisteredState != 0),
+            flag(state & kIOServiceMatchedState != 0),
+            flag(state & kIOServiceInactiveState == 0)
+        );
+    }
+
+    if let Some(busy_state) = entry.busy_state {
+        let _ = write!(out, ", busy {}", busy_state);
+    }
+
+    if let Some(retain_count) = entry.retain_count {
+        let _ = write!(out, ", retain {}", retain_count);
+    }
+
+    out.push('>');
+}
+
+fn write_value(out: &mut String, value: &PropertyValue) {
+    match *value {
+        PropertyValue::Bool(true) => out.push_str("Yes"),
+        PropertyValue::Bool(false) => out.push_str("No"),
+        // CF numbers hold no sign information, and ioreg prints them unsigned
+        PropertyValue::Integer(value) => {
+            let _ = write!(out, "{}", value as u64);
+        }
+        PropertyValue::Float(value) => {
+            let _ = write!(out, "{}", value);
+        }
+        PropertyValue::String(ref value) => {
+            let _ = write!(out, "\"{}\"", value);
+        }
+        PropertyValue::Data(ref value) => write_data(out, value),
+        PropertyValue::Date(value) => {
+            let _ = write!(out, "{}", crate::plist::format_date(value));
+        }
+        PropertyValue::Array(ref values) => {
+            out.push('(');
+
+            for (index, value) in values.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                write_value(out, value);
+            }
+
+            out.push(')');
+        }
+        PropertyValue::Dictionary(ref map) => {
+            out.push('{');
+
+            for (index, (key, value)) in map.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                let _ = write!(out, "\"{}\"=", key);
+                write_value(out, value);
+            }
+
+            out.push('}');
+        }
+    }
+}
+
+/// Writes data as `<"a","b">` when it holds NUL-terminated printable
+/// strings, as device tree properties often do, and as hex otherwise.
+fn write_data(out: &mut String, data: &[u8]) {
+    let strings: Vec<&[u8]> = if data.last() == Some(&0) {
+        data[..data.len() - 1].split(|&b| b == 0).collect()
+    } else {
+        Vec::new()
+    };
+
+    let printable = !strings.is_empty()
+        && strings
+            .iter()
+            .all(|s| !s.is_empty() && s.iter().all(|&b| (0x20..0x7f).contains(&b)));
+
+    out.push('<');
+
+    if printable {
+        for (index, s) in strings.iter().enumerate() {
+            if index > 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "\"{}\"", String::from_utf8_lossy(s));
+        }
+    } else {
+        for b in data {
+            let _ = write!(out, "{:02x}", b);
+        }
+    }
+
+    out.push('>');
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn entry(name: &str, id: u64, children: Vec<SnapshotEntry>) -> SnapshotEntry {
+        SnapshotEntry {
+            name: name.to_string(),
+            class: "IOService".to_string(),
+            id,
+            location: None,
+            retain_count: Some(4),
+            busy_state: Some(0),
+            state: Some(kIOServiceRegisteredState | kIOServiceMatchedState),
+            properties: BTreeMap::new(),
+            children,
+        }
+    }
+
+    #[test]
+    fn labels() {
+        let mut root = entry("Root", 0x100000100, Vec::new());
+        root.location = Some("14".to_string());
+
+        assert_eq!(
+            IORegFormatter::new().format_entry(&root),
+            "+-o Root@14  <class IOService, id 0x100000100, registered, matched, active, \
+             busy 0, retain 4>\n"
+        );
+
+        root.state = Some(kIOServiceInactiveState);
+        root.busy_state = None;
+        root.retain_count = None;
+
+        assert_eq!(
+            IORegFormatter::new().format_entry(&root),
+            "+-o Root@14  <class IOService, id 0x100000100, !registered, !matched, !active>\n"
+        );
+    }
+
+    #[test]
+    fn tree() {
+        let root = entry(
+            "Root",
+            1,
+            vec![
+                entry("a", 2, vec![entry("b", 3, Vec::new())]),
+                entry("c", 4, Vec::new()),
+            ],
+        );
+        let names = |formatter: IORegFormatter| {
+            formatter
+                .format_entry(&root)
+                .lines()
+                .map(|line| line.split("  <").next().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            names(IORegFormatter::new()),
+            vec!["+-o Root", "  +-o a", "  | +-o b", "  +-o c"]
+        );
+        assert_eq!(
+            names(IORegFormatter::new().depth_limit(Some(2))),
+            vec!["+-o Root", "  +-o a", "  +-o c"]
+        );
+    }
+
+    #[test]
+    fn values() {
+        let mut properties = BTreeMap::new();
+        properties.insert("unsigned".to_string(), PropertyValue::Integer(-1));
+        properties.insert(
+            "compatible".to_string(),
+            PropertyValue::Data(b"pci8086,9ded\0pciclass,0c0330\0".to_vec()),
+        );
+        properties.insert(
+            "data".to_string(),
+            PropertyValue::Data(vec![0, 0xe1, 0xf5, 5]),
+        );
+        properties.insert(
+            "list".to_string(),
+            PropertyValue::Array(vec![PropertyValue::Bool(true), "a".into()]),
+        );
+
+        let mut root = entry("Root", 1, Vec::new());
+        root.properties = properties;
+
+        let out = IORegFormatter::new().properties(true).format_entry(&root);
+        let lines = out.lines().skip(1).collect::<Vec<_>>();
+
+        assert_eq!(
+            lines,
+            vec![
+                "    {",
+                "      \"compatible\" = <\"pci8086,9ded\",\"pciclass,0c0330\">",
+                "      \"data\" = <00e1f505>",
+                "      \"list\" = (Yes,\"a\")",
+                "      \"unsigned\" = 18446744073709551615",
+                "    }",
+                "    ",
+            ]
+        );
+    }
+}
@@ -0,0 +1,7 @@
+//! Property keys: the raw `*const c_char` keys of `<IOKit/IOKitKeys.h>`, and
+//! in `typed` the well-known keys of every family with the type of their
+//! values.
+
+pub use io_kit_sys::keys::*;
+
+pub mod typed;
@@ -0,0 +1,199 @@
+//! Well-known property keys with the type of their values, for
+//! `TIOProperties::get` and `TIOProperties::set`. Each is named after, and
+//! built from, the raw key of the same name in `io_kit_sys`.
+
+use std::collections::BTreeMap;
+
+use io_kit_sys::hid::keys as hid;
+use io_kit_sys::keys;
+use io_kit_sys::ps::keys as ps;
+use io_kit_sys::pwr_mgt::pm;
+use io_kit_sys::usb::host as usb_host;
+use io_kit_sys::usb::usb_spec;
+
+use crate::property::{PropertyKey, PropertyValue};
+
+macro_rules! typed_keys {
+    ($($module:ident { $($name:ident: $ty:ty,)* })*) => {
+        $($(
+            pub static $name: PropertyKey<$ty> = unsafe { PropertyKey::from_raw($module::$name) };
+        )*)*
+
+        #[cfg(test)]
+        mod tests {
+            use std::ffi::CStr;
+
+            use super::*;
+
+            #[test]
+            fn names_match_io_kit_sys() {
+                $($(
+                    let raw = unsafe { CStr::from_ptr($module::$name) };
+                    assert_eq!(raw.to_str(), Ok($name.name()), stringify!($name));
+                )*)*
+            }
+        }
+    };
+}
+
+typed_keys! {
+    // <IOKit/IOKitKeys.h>
+    keys {
+        kIORegistryEntryIDKey: u64,
+        kIOClassKey: String,
+        kIOProbeScoreKey: i64,
+        kIOKitDebugKey: u64,
+        kIOProviderClassKey: String,
+        kIONameMatchKey: PropertyValue,
+        kIOPropertyMatchKey: PropertyValue,
+        kIOPathMatchKey: String,
+        kIOLocationMatchKey: BTreeMap<String, PropertyValue>,
+        kIOParentMatchKey: BTreeMap<String, PropertyValue>,
+        kIOResourceMatchKey: String,
+        kIONameMatchedKey: String,
+        kIOMatchCategoryKey: String,
+        kIOUserClientClassKey: String,
+        kIOCFPlugInTypesKey: BTreeMap<String, PropertyValue>,
+        kIOPlatformSerialNumberKey: String,
+        kIOPlatformUUIDKey: String,
+        kIOBootDevicePathKey: String,
+        kIOBootDeviceSizeKey: u64,
+    }
+
+    // <IOKit/IOBSD.h>
+    keys {
+        kIOBSDNameKey: String,
+        kIOBSDNamesKey: BTreeMap<String, PropertyValue>,
+        kIOBSDMajorKey: u32,
+        kIOBSDMinorKey: u32,
+        kIOBSDUnitKey: u32,
+    }
+
+    // <IOKit/storage/IOMedia.h>
+    keys {
+        kIOMediaContentKey: String,
+        kIOMediaContentHintKey: String,
+        kIOMediaEjectableKey: bool,
+        kIOMediaLeafKey: bool,
+        kIOMediaPreferredBlockSizeKey: u64,
+        kIOMediaRemovableKey: bool,
+        kIOMediaSizeKey: u64,
+        kIOMediaUUIDKey: String,
+        kIOMediaWholeKey: bool,
+        kIOMediaWritableKey: bool,
+    }
+
+    // <IOKit/hid/IOHIDKeys.h>
+    hid {
+        kIOHIDTransportKey: String,
+        kIOHIDVendorIDKey: u32,
+        kIOHIDVendorIDSourceKey: u32,
+        kIOHIDProductIDKey: u32,
+        kIOHIDVersionNumberKey: u32,
+        kIOHIDManufacturerKey: String,
+        kIOHIDProductKey: String,
+        kIOHIDSerialNumberKey: String,
+        kIOHIDCountryCodeKey: u32,
+        kIOHIDStandardTypeKey: u32,
+        kIOHIDLocationIDKey: u32,
+        kIOHIDDeviceUsageKey: u32,
+        kIOHIDDeviceUsagePageKey: u32,
+        kIOHIDDeviceUsagePairsKey: Vec<PropertyValue>,
+        kIOHIDPrimaryUsageKey: u32,
+        kIOHIDPrimaryUsagePageKey: u32,
+        kIOHIDMaxInputReportSizeKey: u32,
+        kIOHIDMaxOutputReportSizeKey: u32,
+        kIOHIDMaxFeatureReportSizeKey: u32,
+        kIOHIDReportIntervalKey: u32,
+        kIOHIDSampleIntervalKey: u32,
+        kIOHIDBatchIntervalKey: u32,
+        kIOHIDRequestTimeoutKey: u32,
+        kIOHIDReportDescriptorKey: Vec<u8>,
+        kIOHIDBuiltInKey: bool,
+        kIOHIDCategoryKey: String,
+        kIOHIDUniqueIDKey: u64,
+        kIOHIDPhysicalDeviceUniqueIDKey: String,
+    }
+
+    // <IOKit/ps/IOPSKeys.h>
+    ps {
+        kIOPSPowerSourceIDKey: i64,
+        kIOPSPowerSourceStateKey: String,
+        kIOPSCurrentCapacityKey: i64,
+        kIOPSMaxCapacityKey: i64,
+        kIOPSDesignCapacityKey: i64,
+        kIOPSNominalCapacityKey: i64,
+        kIOPSTimeToEmptyKey: i64,
+        kIOPSTimeToFullChargeKey: i64,
+        kIOPSIsChargingKey: bool,
+        kIOPSInternalFailureKey: bool,
+        kIOPSIsPresentKey: bool,
+        kIOPSVoltageKey: i64,
+        kIOPSCurrentKey: i64,
+        kIOPSTemperatureKey: i64,
+        kIOPSNameKey: String,
+        kIOPSTypeKey: String,
+        kIOPSTransportTypeKey: String,
+        kIOPSVendorIDKey: i64,
+        kIOPSProductIDKey: i64,
+        kIOPSBatteryHealthKey: String,
+        kIOPSIsChargedKey: bool,
+        kIOPSIsFinishingChargeKey: bool,
+    }
+
+    // <IOKit/pwr_mgt/IOPM.h>
+    pm {
+        kIOPMPSExternalConnectedKey: bool,
+        kIOPMPSExternalChargeCapableKey: bool,
+        kIOPMPSBatteryInstalledKey: bool,
+        kIOPMPSIsChargingKey: bool,
+        kIOPMFullyChargedKey: bool,
+        kIOPMPSAtWarnLevelKey: bool,
+        kIOPMPSAtCriticalLevelKey: bool,
+        kIOPMPSCurrentCapacityKey: i64,
+        kIOPMPSMaxCapacityKey: i64,
+        kIOPMPSDesignCapacityKey: i64,
+        kIOPMPSTimeRemainingKey: i64,
+        kIOPMPSAmperageKey: i64,
+        kIOPMPSVoltageKey: i64,
+        kIOPMPSCycleCountKey: i64,
+        kIOPMPSManufacturerKey: String,
+        kIOPMPSModelKey: String,
+        kIOPMPSSerialKey: String,
+        kIOPMDeviceNameKey: String,
+        kIOPMPSBatteryTemperatureKey: i64,
+        kIOPMPSAdapterDetailsKey: BTreeMap<String, PropertyValue>,
+    }
+
+    // <IOKit/usb/USBSpec.h>
+    usb_spec {
+        kUSBDeviceClass: u8,
+        kUSBDeviceSubClass: u8,
+        kUSBDeviceProtocol: u8,
+        kUSBDeviceMaxPacketSize: u8,
+        kUSBVendorID: u16,
+        kUSBProductID: u16,
+        kUSBDeviceReleaseNumber: u16,
+        kUSBManufacturerStringIndex: u8,
+        kUSBProductStringIndex: u8,
+        kUSBSerialNumberStringIndex: u8,
+        kUSBDeviceNumConfigs: u8,
+        kUSBInterfaceNumber: u8,
+        kUSBAlternateSetting: u8,
+        kUSBNumEndpoints: u8,
+        kUSBInterfaceClass: u8,
+        kUSBInterfaceSubClass: u8,
+        kUSBInterfaceProtocol: u8,
+        kUSBInterfaceStringIndex: u8,
+        kUSBProductString: String,
+        kUSBVendorString: String,
+        kUSBSerialNumberString: String,
+    }
+
+    // <IOKit/usb/IOUSBHostFamilyDefinitions.h>
+    usb_host {
+        kUSBDevicePropertyLocationID: u32,
+        kUSBDevicePropertySpeed: u32,
+        kUSBDevicePropertyAddress: u32,
+    }
+}
@@ -1,13 +1,39 @@
 #![allow(non_camel_case_types)]
 #![allow(non_upper_case_globals)]
 
+#[cfg(target_os = "macos")]
 #[macro_use(impl_TCFType)]
 extern crate core_foundation;
-extern crate mach;
+extern crate futures_core;
 
 extern crate io_kit_sys;
 
 pub use io_kit_sys::ret;
 
+// registry snapshots, their matching and formatting, property lists and
+// error codes build on any host; the rest needs IOKit
+#[cfg(target_os = "macos")]
 pub mod base;
+#[cfg(target_os = "macos")]
+pub mod class;
+#[cfg(target_os = "macos")]
+pub mod connect;
+pub mod error;
+#[cfg(target_os = "macos")]
 pub mod hid;
+pub mod ioreg;
+pub mod keys;
+pub mod mach;
+pub mod matching;
+#[cfg(target_os = "macos")]
+pub mod memory;
+#[cfg(target_os = "macos")]
+pub mod notification;
+pub mod plane;
+pub mod plist;
+pub mod property;
+#[cfg(target_os = "macos")]
+mod runloop;
+pub mod snapshot;
+#[cfg(target_os = "macos")]
+pub mod stream;
@@ -0,0 +1,379 @@
+//! Matching dictionaries for `IOService` lookups.
+
+use std::collections::BTreeMap;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+
+#[cfg(target_os = "macos")]
+use core_foundation::{base::TCFType, dictionary::CFDictionary};
+use io_kit_sys::keys::{
+    kIOBSDNameKey, kIOLocationMatchKey, kIONameMatchKey, kIOParentMatchKey, kIOPathMatchKey,
+    kIOPropertyMatchKey, kIOProviderClassKey, kIORegistryEntryIDKey, kIOResourceMatchKey,
+};
+
+use crate::plane::IORegistryPlane;
+use crate::property::PropertyValue;
+use crate::snapshot::{RegistrySnapshot, SnapshotEntry};
+
+pub(crate) fn key(key: *const c_char) -> String {
+    unsafe { CStr::from_ptr(key) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Builds the matching dictionary passed to `IOServiceGetMatchingServices`
+/// and friends. A service matches when it satisfies every criterion set.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Matching {
+    pub(crate) provider_class: Option<String>,
+    pub(crate) names: Vec<String>,
+    // alternatives, any one of which must match in full
+    pub(crate) properties: Vec<BTreeMap<String, PropertyValue>>,
+    pub(crate) path: Option<String>,
+    pub(crate) location: Option<Box<Matching>>,
+    pub(crate) parent: Option<Box<Matching>>,
+    pub(crate) resource: Option<String>,
+    pub(crate) bsd_name: Option<String>,
+    pub(crate) registry_entry_id: Option<u64>,
+}
+
+impl Matching {
+    pub fn new() -> Matching {
+        Matching::default()
+    }
+
+    /// Matches instances of `class` and its subclasses, like `IOServiceMatching`.
+    pub fn class(class: &str) -> Matching {
+        Matching::new().provider_class(class)
+    }
+
+    /// `IOProviderClass`
+    pub fn provider_class(mut self, class: &str) -> Matching {
+        self.provider_class = Some(class.to_string());
+        self
+    }
+
+    /// `IONameMatch`; may be given several times, in which case any of the
+    /// names matches.
+    pub fn name(mut self, name: &str) -> Matching {
+        self.names.push(name.to_string());
+        self
+    }
+
+    /// Adds an entry to `IOPropertyMatch`, or to its last dictionary once
+    /// `properties` has been called.
+    pub fn property<V: Into<PropertyValue>>(mut self, key: &str, value: V) -> Matching {
+        if self.properties.is_empty() {
+            self.properties.push(BTreeMap::new());
+        }
+
+        let last = self.properties.len() - 1;
+        self.properties[last].insert(key.to_string(), value.into());
+        self
+    }
+
+    /// Adds a dictionary to `IOPropertyMatch`. With more than one, it is
+    /// written as an array and a service matches when it has every property
+    /// of any one of the dictionaries.
+    pub fn properties(mut self, properties: BTreeMap<String, PropertyValue>) -> Matching {
+        self.properties.push(properties);
+        self
+    }
+
+    /// `IOPathMatch`
+    pub fn path(mut self, path: &str) -> Matching {
+        self.path = Some(path.to_string());
+        self
+    }
+
+    /// `IOLocationMatch`: the direct provider of the service must match `location`.
+    pub fn location(mut self, location: Matching) -> Matching {
+        self.location = Some(Box::new(location));
+        self
+    }
+
+    /// `IOParentMatch`: some ancestor of the service must match `parent`.
+    pub fn parent(mut self, parent: Matching) -> Matching {
+        self.parent = Some(Box::new(parent));
+        self
+    }
+
+    /// `IOResourceMatch`
+    pub fn resource(mut self, resource: &str) -> Matching {
+        self.resource = Some(resource.to_string());
+        self
+    }
+
+    /// Matches the service published under a BSD name, like `IOBSDNameMatching`.
+    pub fn bsd_name(mut self, bsd_name: &str) -> Matching {
+        self.bsd_name = Some(bsd_name.to_string());
+        self
+    }
+
+    /// Matches a single entry by ID, like `IORegistryEntryIDMatching`.
+    pub fn registry_entry_id(mut self, id: u64) -> Matching {
+        self.registry_entry_id = Some(id);
+        self
+    }
+
+    /// Returns the matching dictionary as a property list value.
+    pub fn to_property_value(&self) -> PropertyValue {
+        let mut map = BTreeMap::new();
+
+        if let Some(ref class) = self.provider_class {
+            map.insert(key(kIOProviderClassKey), class.clone().into());
+        }
+
+        match self.names.len() {
+            0 => {}
+            1 => {
+                map.insert(key(kIONameMatchKey), self.names[0].clone().into());
+            }
+            _ => {
+                let names = self
+                    .names
+                    .iter()
+                    .map(|name| PropertyValue::from(name.clone()))
+                    .collect::<Vec<_>>();
+
+                map.insert(key(kIONameMatchKey), names.into());
+            }
+        }
+
+        match self.properties.len() {
+            0 => {}
+            1 => {
+                map.insert(key(kIOPropertyMatchKey), self.properties[0].clone().into());
+            }
+            _ => {
+                let properties = self
+                    .properties
+                    .iter()
+                    .map(|properties| PropertyValue::from(properties.clone()))
+                    .collect::<Vec<_>>();
+
+                map.insert(key(kIOPropertyMatchKey), properties.into());
+            }
+        }
+        if let Some(ref path) = self.path {
+            map.insert(key(kIOPathMatchKey), path.clone().into());
+        }
+        if let Some(ref location) = self.location {
+            map.insert(key(kIOLocationMatchKey), location.to_property_value());
+        }
+        if let Some(ref parent) = self.parent {
+            map.insert(key(kIOParentMatchKey), parent.to_property_value());
+        }
+        if let Some(ref resource) = self.resource {
+            map.insert(key(kIOResourceMatchKey), resource.clone().into());
+        }
+        if let Some(ref bsd_name) = self.bsd_name {
+            map.insert(key(kIOBSDNameKey), bsd_name.clone().into());
+        }
+        if let Some(id) = self.registry_entry_id {
+            map.insert(key(kIORegistryEntryIDKey), id.into());
+        }
+
+        PropertyValue::Dictionary(map)
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn to_cf_dictionary(&self) -> CFDictionary {
+        let dictionary = self.to_property_value().to_cf_type();
+
+        unsafe { CFDictionary::wrap_under_get_rule(dictionary.as_CFTypeRef() as _) }
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl From<Matching> for CFDictionary {
+    fn from(matching: Matching) -> CFDictionary {
+        matching.to_cf_dictionary()
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl<'a> From<&'a Matching> for CFDictionary {
+    fn from(matching: &'a Matching) -> CFDictionary {
+        matching.to_cf_dictionary()
+    }
+}
+
+pub(crate) fn matching_entries<'a>(
+    snapshot: &'a RegistrySnapshot,
+    matching: &Matching,
+) -> Vec<&'a SnapshotEntry> {
+    let mut entries = Vec::new();
+
+    if let Some(root) = snapshot.root(IORegistryPlane::Service) {
+        let evaluator = Evaluator {
+            snapshot,
+            resources: root.find(|entry| entry.class == "IOResources"),
+        };
+
+        evaluator.collect(root, &mut Vec::new(), matching, &mut entries);
+    }
+
+    entries
+}
+
+/// Applies the rules of `IOService::matchPassive` to a snapshot of the
+/// service plane.
+struct Evaluator<'a> {
+    snapshot: &'a RegistrySnapshot,
+    resources: Option<&'a SnapshotEntry>,
+}
+
+impl<'a> Evaluator<'a> {
+    fn collect(
+        &self,
+        entry: &'a SnapshotEntry,
+        ancestors: &mut Vec<&'a SnapshotEntry>,
+        matching: &Matching,
+        entries: &mut Vec<&'a SnapshotEntry>,
+    ) {
+        if self.matches(entry, ancestors, matching) {
+            entries.push(entry);
+        }
+
+        ancestors.push(entry);
+
+        for child in &entry.children {
+            self.collect(child, ancestors, matching, entries);
+        }
+
+        ancestors.pop();
+    }
+
+    /// `ancestors` runs from the root down to the provider of `entry`.
+    fn matches(
+        &self,
+        entry: &SnapshotEntry,
+        ancestors: &[&SnapshotEntry],
+        matching: &Matching,
+    ) -> bool {
+        if let Some(ref class) = matching.provider_class {
+            if !self.snapshot.conforms_to(&entry.class, class) {
+                return false;
+            }
+        }
+
+        if !matching.names.is_empty()
+            && !matching.names.iter().any(|name| compare_name(entry, name))
+        {
+            return false;
+        }
+
+        // each matched property must compare equal as a whole
+        if !matching.properties.is_empty()
+            && !matching.properties.iter().any(|properties| {
+                properties
+                    .iter()
+                    .all(|(key, value)| entry.properties.get(key) == Some(value))
+            })
+        {
+            return false;
+        }
+
+        if let Some(ref path) = matching.path {
+            let path = if path.ends_with('/') && !path.ends_with(":/") {
+                &path[..path.len() - 1]
+            } else {
+                &path[..]
+            };
+
+            if path_of(entry, ancestors) != path {
+                return false;
+            }
+        }
+
+        if let Some(ref resource) = matching.resource {
+            match self.resources {
+                Some(resources) if resources.properties.contains_key(resource) => {}
+                _ => return false,
+            }
+        }
+
+        if let Some(ref bsd_name) = matching.bsd_name {
+            let value = entry
+                .properties
+                .get(&key(kIOBSDNameKey))
+                .and_then(|value| value.as_str());
+
+            if value != Some(bsd_name) {
+                return false;
+            }
+        }
+
+        if let Some(id) = matching.registry_entry_id {
+            if entry.id != id {
+                return false;
+            }
+        }
+
+        if let Some(ref location) = matching.location {
+            match ancestors.split_last() {
+                Some((provider, ancestors)) if self.matches(provider, ancestors, location) => {}
+                _ => return false,
+            }
+        }
+
+        if let Some(ref parent) = matching.parent {
+            if !(0..ancestors.len()).any(|i| self.matches(ancestors[i], &ancestors[..i], parent)) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// `IONameMatch` compares against the entry name and the device tree
+/// `name` and `compatible` properties.
+fn compare_name(entry: &SnapshotEntry, name: &str) -> bool {
+    if entry.name == name {
+        return true;
+    }
+
+    ["name", "compatible"]
+        .iter()
+        .filter_map(|key| entry.properties.get(*key))
+        .any(|value| names_of(value).iter().any(|n| n == name))
+}
+
+/// Device tree properties hold NUL-separated strings in data.
+fn names_of(value: &PropertyValue) -> Vec<String> {
+    match *value {
+        PropertyValue::String(ref name) => vec![name.clone()],
+        PropertyValue::Data(ref data) => data
+            .split(|&b| b == 0)
+            .filter(|name| !name.is_empty())
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+            .collect(),
+        PropertyValue::Array(ref values) => values.iter().flat_map(names_of).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Builds the path `IORegistryEntryGetPath` returns in the service plane.
+fn path_of(entry: &SnapshotEntry, ancestors: &[&SnapshotEntry]) -> String {
+    let mut path = format!("{}:", IORegistryPlane::Service.name());
+
+    if ancestors.is_empty() {
+        path.push('/');
+        return path;
+    }
+
+    // the root itself does not appear in paths
+    for entry in ancestors.iter().skip(1).cloned().chain(Some(entry)) {
+        path.push('/');
+        path.push_str(&entry.name);
+
+        if let Some(ref location) = entry.location {
+            path.push('@');
+            path.push_str(location);
+        }
+    }
+
+    path
+}
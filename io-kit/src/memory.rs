@@ -0,0 +1,238 @@
+//! Memory shared by user clients through `IOConnectMapMemory64`.
+
+use std::marker::PhantomData;
+use std::mem;
+use std::os::raw::c_int;
+use std::ptr;
+
+use io_kit_sys::ret::kIOReturnSuccess;
+use io_kit_sys::types::{io_connect_t, kIOMapAnywhere, kIOMapReadOnly, IOOptionBits};
+use io_kit_sys::*;
+use mach::traps::mach_task_self;
+use mach::vm_types::{mach_vm_address_t, mach_vm_size_t};
+
+use crate::connect::IOConnection;
+use crate::error::Error;
+
+/// Types that any bit pattern is a valid value of, and so can be read from
+/// shared memory.
+///
+/// # Safety
+///
+/// Implementors must have no padding and no invalid bit patterns.
+pub unsafe trait MappedValue: Copy {}
+
+unsafe impl MappedValue for u8 {}
+unsafe impl MappedValue for u16 {}
+unsafe impl MappedValue for u32 {}
+unsafe impl MappedValue for u64 {}
+unsafe impl MappedValue for usize {}
+unsafe impl MappedValue for i8 {}
+unsafe impl MappedValue for i16 {}
+unsafe impl MappedValue for i32 {}
+unsafe impl MappedValue for i64 {}
+unsafe impl MappedValue for isize {}
+unsafe impl MappedValue for f32 {}
+unsafe impl MappedValue for f64 {}
+unsafe impl<T: MappedValue, const N: usize> MappedValue for [T; N] {}
+
+/// A mapping of user client memory into the current task, unmapped on drop.
+pub struct MappedMemory {
+    // an extra reference keeps the connection port valid for unmapping
+    connect: io_connect_t,
+    memory_type: u32,
+    address: mach_vm_address_t,
+    size: mach_vm_size_t,
+    read_only: bool,
+}
+
+impl Drop for MappedMemory {
+    fn drop(&mut self) {
+        // if this fails the mapping is left in place: the range may since
+        // hold something else, so it is not deallocated blindly
+        unsafe {
+            IOConnectUnmapMemory64(
+                self.connect,
+                self.memory_type,
+                mach_task_self(),
+                self.address,
+            );
+            IOConnectRelease(self.connect);
+        }
+    }
+}
+
+impl MappedMemory {
+    /// Maps the memory of `memory_type` anywhere in the current task.
+    /// `options` takes the `kIOMap*` flags of `io_kit_sys::types`, such as
+    /// `kIOMapReadOnly` or a cache mode like `kIOMapWriteCombineCache`.
+    pub fn map(
+        connection: &IOConnection,
+        memory_type: u32,
+        options: c_int,
+    ) -> Result<MappedMemory, Error> {
+        let connect = connection.as_connect_t();
+        let mut address: mach_vm_address_t = 0;
+        let mut size: mach_vm_size_t = 0;
+
+        unsafe {
+            let result = IOConnectAddRef(connect);
+
+            if result != kIOReturnSuccess {
+                return Err(Error::new(result));
+            }
+
+            let result = IOConnectMapMemory64(
+                connect,
+                memory_type,
+                mach_task_self(),
+                &mut address,
+                &mut size,
+                (options | kIOMapAnywhere) as IOOptionBits,
+            );
+
+            if result == kIOReturnSuccess {
+                Ok(MappedMemory {
+                    connect,
+                    memory_type,
+                    address,
+                    size,
+                    read_only: options & kIOMapReadOnly != 0,
+                })
+            } else {
+                IOConnectRelease(connect);
+                Err(Error::new(result))
+            }
+        }
+    }
+
+    pub fn address(&self) -> mach_vm_address_t {
+        self.address
+    }
+
+    pub fn len(&self) -> usize {
+        self.size as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// The start of the mapping. The driver may change the memory at any
+    /// time, so it must not be read through references.
+    pub fn as_ptr(&self) -> *const u8 {
+        self.address as *const u8
+    }
+
+    /// Returns `None` for read-only mappings.
+    pub fn as_mut_ptr(&mut self) -> Option<*mut u8> {
+        if self.read_only {
+            None
+        } else {
+            Some(self.address as *mut u8)
+        }
+    }
+
+    /// Copies `len` bytes from `offset` with volatile loads, or returns
+    /// `None` if they are out of bounds.
+    pub fn read_bytes(&self, offset: usize, len: usize) -> Option<Vec<u8>> {
+        let ptr = self.bytes_at(offset, len)?;
+
+        Some(
+            (0..len)
+                .map(|i| unsafe { ptr::read_volatile(ptr.add(i)) })
+                .collect(),
+        )
+    }
+
+    /// Copies `bytes` to `offset` with volatile stores. Returns `false`,
+    /// writing nothing, if they are out of bounds or the mapping is
+    /// read-only.
+    pub fn write_bytes(&mut self, offset: usize, bytes: &[u8]) -> bool {
+        let ptr = match self.bytes_at(offset, bytes.len()) {
+            Some(ptr) if !self.read_only => ptr,
+            _ => return false,
+        };
+
+        for (i, &byte) in bytes.iter().enumerate() {
+            unsafe { ptr::write_volatile(ptr.add(i), byte) }
+        }
+
+        true
+    }
+
+    /// Returns a volatile view of the `T` at `offset`, or `None` if it is out
+    /// of bounds or misaligned.
+    pub fn volatile<T: MappedValue>(&self, offset: usize) -> Option<Volatile<'_, T>> {
+        self.pointer_to::<T>(offset).map(|ptr| Volatile {
+            ptr,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Returns a writable volatile view of the `T` at `offset`, or `None` if
+    /// it is out of bounds or misaligned or the mapping is read-only.
+    pub fn volatile_mut<T: MappedValue>(&mut self, offset: usize) -> Option<VolatileMut<'_, T>> {
+        if self.read_only {
+            return None;
+        }
+
+        self.pointer_to::<T>(offset).map(|ptr| VolatileMut {
+            ptr,
+            _marker: PhantomData,
+        })
+    }
+
+    fn pointer_to<T>(&self, offset: usize) -> Option<*mut T> {
+        let ptr = self.bytes_at(offset, mem::size_of::<T>())? as *mut T;
+
+        // alignments are powers of two
+        if ptr as usize & (mem::align_of::<T>() - 1) == 0 {
+            Some(ptr)
+        } else {
+            None
+        }
+    }
+
+    /// Returns a pointer to the `len` bytes at `offset`, if they are in bounds.
+    fn bytes_at(&self, offset: usize, len: usize) -> Option<*mut u8> {
+        if offset.checked_add(len)? <= self.len() {
+            Some((self.address as usize + offset) as *mut u8)
+        } else {
+            None
+        }
+    }
+}
+
+/// A value in shared memory, read with volatile loads as the other side may
+/// change it at any time.
+pub struct Volatile<'a, T> {
+    ptr: *const T,
+    _marker: PhantomData<&'a T>,
+}
+
+impl<'a, T: MappedValue> Volatile<'a, T> {
+    pub fn read(&self) -> T {
+        unsafe { ptr::read_volatile(self.ptr) }
+    }
+}
+
+/// A value in shared memory, accessed with volatile loads and stores.
+pub struct VolatileMut<'a, T> {
+    ptr: *mut T,
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T: MappedValue> VolatileMut<'a, T> {
+    pub fn read(&self) -> T {
+        unsafe { ptr::read_volatile(self.ptr) }
+    }
+
+    pub fn write(&mut self, value: T) {
+        unsafe { ptr::write_volatile(self.ptr, value) }
+    }
+}
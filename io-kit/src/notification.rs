@@ -0,0 +1,365 @@
+//! Service notifications delivered through an `IONotificationPort`.
+
+use std::os::raw::{c_char, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+
+use core_foundation::base::{CFRetain, TCFType};
+use core_foundation::dictionary::CFDictionary;
+use core_foundation::runloop::{kCFRunLoopDefaultMode, CFRunLoop, CFRunLoopSource};
+use io_kit_sys::base::dispatch_queue_t;
+use io_kit_sys::keys::{
+    kIOAppPowerStateInterest, kIOBusyInterest, kIOFirstMatchNotification,
+    kIOFirstPublishNotification, kIOGeneralInterest, kIOMatchedNotification,
+    kIOPriorityPowerStateInterest, kIOPublishNotification, kIOTerminatedNotification,
+};
+use io_kit_sys::message::*;
+use io_kit_sys::pwr_mgt::pm::IOPowerStateChangeNotification;
+use io_kit_sys::ret::kIOReturnError;
+use io_kit_sys::types::{io_iterator_t, io_object_t, io_service_t};
+use io_kit_sys::*;
+use mach::kern_return::KERN_SUCCESS;
+use mach::port::mach_port_t;
+
+use crate::base::{IOIterator, IOObject, IOService, TIOObject};
+use crate::error::Error;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NotificationType {
+    FirstPublish,
+    Publish,
+    FirstMatch,
+    Matched,
+    Terminated,
+}
+
+impl NotificationType {
+    pub fn as_ptr(&self) -> *mut c_char {
+        let name = match *self {
+            NotificationType::FirstPublish => kIOFirstPublishNotification,
+            NotificationType::Publish => kIOPublishNotification,
+            NotificationType::FirstMatch => kIOFirstMatchNotification,
+            NotificationType::Matched => kIOMatchedNotification,
+            NotificationType::Terminated => kIOTerminatedNotification,
+        };
+
+        name as *mut c_char
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum InterestType {
+    General,
+    Busy,
+    AppPowerState,
+    PriorityPowerState,
+}
+
+impl InterestType {
+    pub fn as_ptr(&self) -> *mut c_char {
+        let name = match *self {
+            InterestType::General => kIOGeneralInterest,
+            InterestType::Busy => kIOBusyInterest,
+            InterestType::AppPowerState => kIOAppPowerStateInterest,
+            InterestType::PriorityPowerState => kIOPriorityPowerStateInterest,
+        };
+
+        name as *mut c_char
+    }
+}
+
+/// The power state a device is moving to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PowerStateChange {
+    pub state_number: u64,
+    pub state_flags: u64,
+}
+
+/// A message delivered to an interest notification.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ServiceMessage {
+    Terminated,
+    Suspended,
+    Resumed,
+    RequestingClose,
+    AttemptingOpen,
+    WasClosed,
+    /// The new busy state of the service.
+    BusyStateChange(u32),
+    PropertyChange,
+    CanDevicePowerOff(Option<PowerStateChange>),
+    DeviceWillPowerOff(Option<PowerStateChange>),
+    DeviceWillNotPowerOff(Option<PowerStateChange>),
+    DeviceHasPoweredOn(Option<PowerStateChange>),
+    /// The system power messages carry the notification ID to pass to
+    /// `IOAllowPowerChange` or `IOCancelPowerChange`.
+    CanSystemSleep(isize),
+    SystemWillSleep(isize),
+    SystemWillNotSleep(isize),
+    SystemWillPowerOn(isize),
+    SystemHasPoweredOn(isize),
+    SystemWillPowerOff(isize),
+    SystemWillRestart(isize),
+    Other(u32),
+}
+
+impl ServiceMessage {
+    /// Decodes a message as passed to an `IOServiceInterestCallback`.
+    ///
+    /// # Safety
+    ///
+    /// `argument` must be the message argument IOKit delivered along with
+    /// `message_type`.
+    pub unsafe fn from_raw(message_type: u32, argument: *mut c_void) -> ServiceMessage {
+        let power_state_change = || {
+            (argument as *const IOPowerStateChangeNotification)
+                .as_ref()
+                .map(|change| PowerStateChange {
+                    state_number: change.stateNumber,
+                    state_flags: change.stateFlags,
+                })
+        };
+        let notification_id = argument as isize;
+
+        match message_type {
+            kIOMessageServiceIsTerminated => ServiceMessage::Terminated,
+            kIOMessageServiceIsSuspended => ServiceMessage::Suspended,
+            kIOMessageServiceIsResumed => ServiceMessage::Resumed,
+            kIOMessageServiceIsRequestingClose => ServiceMessage::RequestingClose,
+            kIOMessageServiceIsAttemptingOpen => ServiceMessage::AttemptingOpen,
+            kIOMessageServiceWasClosed => ServiceMessage::WasClosed,
+            kIOMessageServiceBusyStateChange => {
+                ServiceMessage::BusyStateChange(argument as usize as u32)
+            }
+            kIOMessageServicePropertyChange => ServiceMessage::PropertyChange,
+            kIOMessageCanDevicePowerOff => ServiceMessage::CanDevicePowerOff(power_state_change()),
+            kIOMessageDeviceWillPowerOff => {
+                ServiceMessage::DeviceWillPowerOff(power_state_change())
+            }
+            kIOMessageDeviceWillNotPowerOff => {
+                ServiceMessage::DeviceWillNotPowerOff(power_state_change())
+            }
+            kIOMessageDeviceHasPoweredOn => {
+                ServiceMessage::DeviceHasPoweredOn(power_state_change())
+            }
+            kIOMessageCanSystemSleep => ServiceMessage::CanSystemSleep(notification_id),
+            kIOMessageSystemWillSleep => ServiceMessage::SystemWillSleep(notification_id),
+            kIOMessageSystemWillNotSleep => ServiceMessage::SystemWillNotSleep(notification_id),
+            kIOMessageSystemWillPowerOn => ServiceMessage::SystemWillPowerOn(notification_id),
+            kIOMessageSystemHasPoweredOn => ServiceMessage::SystemHasPoweredOn(notification_id),
+            kIOMessageSystemWillPowerOff => ServiceMessage::SystemWillPowerOff(notification_id),
+            kIOMessageSystemWillRestart => ServiceMessage::SystemWillRestart(notification_id),
+            message_type => ServiceMessage::Other(message_type),
+        }
+    }
+}
+
+// shared with the subscriptions, which must not outlive the port
+struct PortRef(IONotificationPortRef);
+
+impl Drop for PortRef {
+    fn drop(&mut self) {
+        unsafe { IONotificationPortDestroy(self.0) }
+    }
+}
+
+/// The port is destroyed once it and every subscription made on it have been
+/// dropped.
+pub struct NotificationPort(Rc<PortRef>);
+
+impl NotificationPort {
+    pub fn new() -> Option<NotificationPort> {
+        unsafe {
+            let result = IONotificationPortCreate(kIOMasterPortDefault);
+
+            if result.is_null() {
+                None
+            } else {
+                Some(NotificationPort(Rc::new(PortRef(result))))
+            }
+        }
+    }
+
+    /// Returns the source to add to a run loop for notifications to be
+    /// delivered on it.
+    pub fn get_run_loop_source(&self) -> Option<CFRunLoopSource> {
+        unsafe {
+            let result = IONotificationPortGetRunLoopSource(self.as_ptr());
+
+            if result.is_null() {
+                None
+            } else {
+                Some(TCFType::wrap_under_get_rule(result))
+            }
+        }
+    }
+
+    /// Delivers notifications on `queue` instead of a run loop.
+    ///
+    /// # Safety
+    ///
+    /// The callbacks of the port's subscriptions then run on the queue's
+    /// threads: they must be safe to call from those threads, and no
+    /// subscription may be dropped while the queue could be running its
+    /// callback.
+    pub unsafe fn set_dispatch_queue(&self, queue: dispatch_queue_t) {
+        IONotificationPortSetDispatchQueue(self.as_ptr(), queue)
+    }
+
+    pub fn get_mach_port(&self) -> mach_port_t {
+        unsafe { IONotificationPortGetMachPort(self.as_ptr()) }
+    }
+
+    pub fn as_ptr(&self) -> IONotificationPortRef {
+        (self.0).0
+    }
+
+    /// Adds the port's source to `run_loop` in the default mode.
+    pub fn schedule_with_run_loop(&self, run_loop: &CFRunLoop) -> Result<(), Error> {
+        let source = self
+            .get_run_loop_source()
+            .ok_or(Error::new(kIOReturnError))?;
+        run_loop.add_source(&source, unsafe { kCFRunLoopDefaultMode });
+        Ok(())
+    }
+
+    /// Calls `callback` with every service matching `matching` for which
+    /// `notification_type` fires. Services that already match are delivered
+    /// before this returns, as draining them arms the notification.
+    ///
+    /// The subscription lasts until the returned value is dropped, and keeps
+    /// the port alive until then.
+    pub fn add_matching_notification<M, F>(
+        &self,
+        notification_type: NotificationType,
+        matching: M,
+        callback: F,
+    ) -> Result<MatchingNotification, Error>
+    where
+        M: Into<CFDictionary>,
+        F: FnMut(IOService) + 'static,
+    {
+        let matching = matching.into();
+        let mut callback: Box<Box<dyn FnMut(IOService)>> = Box::new(Box::new(callback));
+
+        unsafe {
+            let mut notification: io_iterator_t = 0;
+
+            // the call consumes one reference to the dictionary
+            CFRetain(matching.as_CFTypeRef());
+
+            let result = IOServiceAddMatchingNotification(
+                self.as_ptr(),
+                notification_type.as_ptr(),
+                matching.as_CFTypeRef() as _,
+                matching_callback,
+                &mut *callback as *mut Box<dyn FnMut(IOService)> as *mut c_void,
+                &mut notification,
+            );
+
+            if result != KERN_SUCCESS {
+                return Err(Error::new(result));
+            }
+
+            drain(notification, &mut callback);
+
+            Ok(MatchingNotification {
+                iterator: IOIterator(notification),
+                _callback: callback,
+                _port: self.0.clone(),
+            })
+        }
+    }
+
+    /// Calls `callback` with every message `service` sends for `interest_type`,
+    /// until the returned value, which keeps the port alive, is dropped.
+    pub fn add_interest_notification<F>(
+        &self,
+        service: &IOService,
+        interest_type: InterestType,
+        callback: F,
+    ) -> Result<InterestNotification, Error>
+    where
+        F: FnMut(ServiceMessage) + 'static,
+    {
+        let mut callback: Box<Box<dyn FnMut(ServiceMessage)>> = Box::new(Box::new(callback));
+
+        unsafe {
+            let mut notification: io_object_t = 0;
+
+            let result = IOServiceAddInterestNotification(
+                self.as_ptr(),
+                service.as_io_object_t(),
+                interest_type.as_ptr(),
+                interest_callback,
+                &mut *callback as *mut Box<dyn FnMut(ServiceMessage)> as *mut c_void,
+                &mut notification,
+            );
+
+            if result != KERN_SUCCESS {
+                return Err(Error::new(result));
+            }
+
+            Ok(InterestNotification {
+                _notification: IOObject(notification),
+                _callback: callback,
+                _port: self.0.clone(),
+            })
+        }
+    }
+}
+
+/// A subscription made with `NotificationPort::add_matching_notification`.
+pub struct MatchingNotification {
+    // released first, so no callback can fire once the closure is freed
+    iterator: IOIterator,
+    _callback: Box<Box<dyn FnMut(IOService)>>,
+    _port: Rc<PortRef>,
+}
+
+impl MatchingNotification {
+    pub fn iterator(&self) -> &IOIterator {
+        &self.iterator
+    }
+}
+
+/// A subscription made with `NotificationPort::add_interest_notification`.
+pub struct InterestNotification {
+    // released first, so no callback can fire once the closure is freed
+    _notification: IOObject,
+    _callback: Box<Box<dyn FnMut(ServiceMessage)>>,
+    _port: Rc<PortRef>,
+}
+
+unsafe extern "C" fn interest_callback(
+    refcon: *mut c_void,
+    _service: io_service_t,
+    message_type: u32,
+    message_argument: *mut c_void,
+) {
+    let callback = &mut *(refcon as *mut Box<dyn FnMut(ServiceMessage)>);
+    let message = ServiceMessage::from_raw(message_type, message_argument);
+
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| callback(message)));
+}
+
+unsafe extern "C" fn matching_callback(refcon: *mut c_void, iterator: io_iterator_t) {
+    let callback = &mut *(refcon as *mut Box<dyn FnMut(IOService)>);
+
+    drain(iterator, callback);
+}
+
+/// Hands every pending service to `callback`. The iterator must be emptied for
+/// IOKit to deliver the next notification, so a panicking callback only loses
+/// the service it was given.
+unsafe fn drain(iterator: io_iterator_t, callback: &mut Box<dyn FnMut(IOService)>) {
+    loop {
+        let result = IOIteratorNext(iterator);
+
+        if result == 0 {
+            break;
+        }
+
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| callback(IOService(result))));
+    }
+}
@@ -0,0 +1,59 @@
+//! The planes of the I/O Registry, kept apart from `base` so that snapshots
+//! and matching can name them on any host.
+
+use std::os::raw::c_char;
+
+use io_kit_sys::keys::{
+    kIOAudioPlane, kIODeviceTreePlane, kIOFireWirePlane, kIOPowerPlane, kIOServicePlane,
+    kIOUSBPlane,
+};
+
+/// A plane of the I/O Registry, as named in `IOKitKeys.h`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum IORegistryPlane {
+    Service,
+    Power,
+    DeviceTree,
+    Audio,
+    FireWire,
+    USB,
+}
+
+impl IORegistryPlane {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            IORegistryPlane::Service => "IOService",
+            IORegistryPlane::Power => "IOPower",
+            IORegistryPlane::DeviceTree => "IODeviceTree",
+            IORegistryPlane::Audio => "IOAudio",
+            IORegistryPlane::FireWire => "IOFireWire",
+            IORegistryPlane::USB => "IOUSB",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<IORegistryPlane> {
+        match name {
+            "IOService" => Some(IORegistryPlane::Service),
+            "IOPower" => Some(IORegistryPlane::Power),
+            "IODeviceTree" => Some(IORegistryPlane::DeviceTree),
+            "IOAudio" => Some(IORegistryPlane::Audio),
+            "IOFireWire" => Some(IORegistryPlane::FireWire),
+            "IOUSB" => Some(IORegistryPlane::USB),
+            _ => None,
+        }
+    }
+
+    /// Returns the plane name as the `io_name_t` expected by the registry functions.
+    pub fn as_ptr(&self) -> *mut c_char {
+        let name = match *self {
+            IORegistryPlane::Service => kIOServicePlane,
+            IORegistryPlane::Power => kIOPowerPlane,
+            IORegistryPlane::DeviceTree => kIODeviceTreePlane,
+            IORegistryPlane::Audio => kIOAudioPlane,
+            IORegistryPlane::FireWire => kIOFireWirePlane,
+            IORegistryPlane::USB => kIOUSBPlane,
+        };
+
+        name as *mut c_char
+    }
+}
@@ -0,0 +1,749 @@
+//! Reading and writing `PropertyValue`s as XML property lists, the format
+//! produced by `ioreg -a` and `plutil -convert xml1`.
+
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt;
+
+use crate::property::PropertyValue;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlistError {
+    message: String,
+    offset: usize,
+}
+
+impl PlistError {
+    pub(crate) fn new<S: Into<String>>(message: S, offset: usize) -> PlistError {
+        PlistError {
+            message: message.into(),
+            offset,
+        }
+    }
+
+    /// Byte offset into the document at which the error was detected.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl fmt::Display for PlistError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.offset)
+    }
+}
+
+impl error::Error for PlistError {}
+
+const HEADER: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+<plist version=\"1.0\">\n";
+
+/// Serializes `value` as a complete XML property list document.
+pub fn to_xml(value: &PropertyValue) -> String {
+    let mut out = String::from(HEADER);
+    write_value(&mut out, value, 0);
+    out.push_str("</plist>\n");
+    out
+}
+
+/// Parses a complete XML property list document.
+pub fn from_xml(xml: &str) -> Result<PropertyValue, PlistError> {
+    let mut parser = Parser { xml, pos: 0 };
+
+    match parser.next_token()? {
+        Token::Start("plist", false) => {}
+        _ => return Err(PlistError::new("expected <plist>", parser.pos)),
+    }
+
+    let value = parser.parse_value()?;
+
+    match parser.next_token()? {
+        Token::End("plist") => {}
+        _ => return Err(PlistError::new("expected </plist>", parser.pos)),
+    }
+
+    match parser.next_token()? {
+        Token::Eof => Ok(value),
+        _ => Err(PlistError::new(
+            "trailing content after </plist>",
+            parser.pos,
+        )),
+    }
+}
+
+fn indent(out: &mut String, level: usize) {
+    for _ in 0..level {
+        out.push('\t');
+    }
+}
+
+fn write_value(out: &mut String, value: &PropertyValue, level: usize) {
+    indent(out, level);
+
+    match *value {
+        PropertyValue::Bool(true) => out.push_str("<true/>\n"),
+        PropertyValue::Bool(false) => out.push_str("<false/>\n"),
+        PropertyValue::Integer(value) => {
+            out.push_str(&format!("<integer>{}</integer>\n", value));
+        }
+        PropertyValue::Float(value) => {
+            out.push_str(&format!("<real>{}</real>\n", format_real(value)));
+        }
+        PropertyValue::String(ref value) => {
+            out.push_str("<string>");
+            escape(out, value);
+            out.push_str("</string>\n");
+        }
+        PropertyValue::Data(ref value) => {
+            out.push_str("<data>\n");
+
+            for line in encode_base64(value).as_bytes().chunks(68) {
+                indent(out, level);
+                out.push_str(std::str::from_utf8(line).unwrap());
+                out.push('\n');
+            }
+
+            indent(out, level);
+            out.push_str("</data>\n");
+        }
+        PropertyValue::Date(value) => {
+            out.push_str(&format!("<date>{}</date>\n", format_date(value)));
+        }
+        PropertyValue::Array(ref values) if values.is_empty() => out.push_str("<array/>\n"),
+        PropertyValue::Array(ref values) => {
+            out.push_str("<array>\n");
+
+            for value in values {
+                write_value(out, value, level + 1);
+            }
+
+            indent(out, level);
+            out.push_str("</array>\n");
+        }
+        PropertyValue::Dictionary(ref map) if map.is_empty() => out.push_str("<dict/>\n"),
+        PropertyValue::Dictionary(ref map) => {
+            out.push_str("<dict>\n");
+
+            for (key, value) in map {
+                indent(out, level + 1);
+                out.push_str("<key>");
+                escape(out, key);
+                out.push_str("</key>\n");
+                write_value(out, value, level + 1);
+            }
+
+            indent(out, level);
+            out.push_str("</dict>\n");
+        }
+    }
+}
+
+fn format_real(value: f64) -> String {
+    if value.is_nan() {
+        "nan".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 {
+            "+infinity"
+        } else {
+            "-infinity"
+        }
+        .to_string()
+    } else {
+        format!("{}", value)
+    }
+}
+
+fn escape(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '&' => out.push_str("&amp;"),
+            c => out.push(c),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    /// A start tag; the flag is set for empty-element tags such as `<true/>`.
+    Start(&'a str, bool),
+    End(&'a str),
+    Eof,
+}
+
+struct Parser<'a> {
+    xml: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.xml[self.pos..]
+    }
+
+    fn skip_until(&mut self, terminator: &str) -> Result<(), PlistError> {
+        match self.rest().find(terminator) {
+            Some(index) => {
+                self.pos += index + terminator.len();
+                Ok(())
+            }
+            None => Err(PlistError::new(
+                format!("unterminated markup, expected {:?}", terminator),
+                self.pos,
+            )),
+        }
+    }
+
+    /// Returns the next tag, skipping whitespace, the XML declaration, the
+    /// doctype and comments.
+    fn next_token(&mut self) -> Result<Token<'a>, PlistError> {
+        loop {
+            let trimmed = self.rest().trim_start();
+            self.pos = self.xml.len() - trimmed.len();
+
+            if trimmed.is_empty() {
+                return Ok(Token::Eof);
+            }
+
+            if trimmed.starts_with("<?") {
+                self.skip_until("?>")?;
+            } else if trimmed.starts_with("<!--") {
+                self.skip_until("-->")?;
+            } else if trimmed.starts_with("<!") {
+                self.skip_until(">")?;
+            } else if trimmed.starts_with('<') {
+                let start = self.pos;
+                self.skip_until(">")?;
+                let tag = &self.xml[start + 1..self.pos - 1];
+
+                if let Some(name) = tag.strip_prefix('/') {
+                    return Ok(Token::End(name.trim()));
+                }
+
+                let (tag, empty) = match tag.strip_suffix('/') {
+                    Some(tag) => (tag, true),
+                    None => (tag, false),
+                };
+                let name = tag.split_whitespace().next().unwrap_or("");
+
+                return Ok(Token::Start(name, empty));
+            } else {
+                return Err(PlistError::new("unexpected text", self.pos));
+            }
+        }
+    }
+
+    /// Reads character data up to the closing tag of `name`.
+    fn text(&mut self, name: &str) -> Result<String, PlistError> {
+        let start = self.pos;
+        let end = match self.rest().find('<') {
+            Some(index) => start + index,
+            None => return Err(PlistError::new("unterminated element", start)),
+        };
+        self.pos = end;
+
+        let text = unescape(&self.xml[start..end], start)?;
+
+        match self.next_token()? {
+            Token::End(end_name) if end_name == name => Ok(text),
+            _ => Err(PlistError::new(format!("expected </{}>", name), end)),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<PropertyValue, PlistError> {
+        let start = self.xml.len() - self.rest().trim_start().len();
+        let (name, empty) = match self.next_token()? {
+            Token::Start(name, empty) => (name, empty),
+            _ => return Err(PlistError::new("expected a value", start)),
+        };
+
+        match (name, empty) {
+            ("true", true) => Ok(PropertyValue::Bool(true)),
+            ("false", true) => Ok(PropertyValue::Bool(false)),
+            ("string", true) => Ok(PropertyValue::String(String::new())),
+            ("data", true) => Ok(PropertyValue::Data(Vec::new())),
+            ("array", true) => Ok(PropertyValue::Array(Vec::new())),
+            ("dict", true) => Ok(PropertyValue::Dictionary(BTreeMap::new())),
+            ("string", false) => self.text(name).map(PropertyValue::String),
+            ("integer", false) => {
+                let text = self.text(name)?;
+                parse_integer(text.trim())
+                    .map(PropertyValue::Integer)
+                    .ok_or_else(|| PlistError::new(format!("invalid integer {:?}", text), start))
+            }
+            ("real", false) => {
+                let text = self.text(name)?;
+                parse_real(text.trim())
+                    .map(PropertyValue::Float)
+                    .ok_or_else(|| PlistError::new(format!("invalid real {:?}", text), start))
+            }
+            ("data", false) => {
+                let text = self.text(name)?;
+                decode_base64(&text)
+                    .map(PropertyValue::Data)
+                    .ok_or_else(|| PlistError::new("invalid base64 data", start))
+            }
+            ("date", false) => {
+                let text = self.text(name)?;
+                parse_date(text.trim())
+                    .map(PropertyValue::Date)
+                    .ok_or_else(|| PlistError::new(format!("invalid date {:?}", text), start))
+            }
+            ("array", false) => {
+                let mut values = Vec::new();
+
+                loop {
+                    let before = self.pos;
+
+                    if let Token::End("array") = self.next_token()? {
+                        return Ok(PropertyValue::Array(values));
+                    }
+
+                    self.pos = before;
+                    values.push(self.parse_value()?);
+                }
+            }
+            ("dict", false) => {
+                let mut map = BTreeMap::new();
+
+                loop {
+                    let before = self.pos;
+
+                    match self.next_token()? {
+                        Token::End("dict") => return Ok(PropertyValue::Dictionary(map)),
+                        Token::Start("key", false) => {
+                            let key = self.text("key")?;
+                            let value = self.parse_value()?;
+                            map.insert(key, value);
+                        }
+                        _ => return Err(PlistError::new("expected <key>", before)),
+                    }
+                }
+            }
+            _ => Err(PlistError::new(format!("unexpected <{}>", name), start)),
+        }
+    }
+}
+
+fn unescape(text: &str, offset: usize) -> Result<String, PlistError> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(index) = rest.find('&') {
+        out.push_str(&rest[..index]);
+        rest = &rest[index..];
+
+        let end = rest
+            .find(';')
+            .ok_or_else(|| PlistError::new("unterminated entity", offset))?;
+        let entity = &rest[1..end];
+
+        let c = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => {
+                let code = if let Some(hex) = entity.strip_prefix("#x") {
+                    u32::from_str_radix(hex, 16).ok()
+                } else if let Some(decimal) = entity.strip_prefix('#') {
+                    decimal.parse().ok()
+                } else {
+                    None
+                };
+
+                code.and_then(std::char::from_u32)
+            }
+        };
+
+        match c {
+            Some(c) => out.push(c),
+            None => {
+                return Err(PlistError::new(
+                    format!("unknown entity &{};", entity),
+                    offset,
+                ))
+            }
+        }
+
+        rest = &rest[end + 1..];
+    }
+
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Parses a decimal or `0x` hexadecimal integer. Values above `i64::MAX`, as
+/// written for unsigned 64-bit numbers, wrap into the negative range.
+fn parse_integer(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+
+    let magnitude = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+        None => digits.parse::<u64>().ok()?,
+    };
+
+    if negative {
+        if magnitude > i64::MAX as u64 + 1 {
+            None
+        } else {
+            Some((magnitude as i64).wrapping_neg())
+        }
+    } else {
+        Some(magnitude as i64)
+    }
+}
+
+fn parse_real(text: &str) -> Option<f64> {
+    match text {
+        "nan" => Some(f64::NAN),
+        "+infinity" | "infinity" => Some(f64::INFINITY),
+        "-infinity" => Some(f64::NEG_INFINITY),
+        _ => text.parse().ok(),
+    }
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn encode_base64(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).cloned().unwrap_or(0),
+            chunk.get(2).cloned().unwrap_or(0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    let mut n = 0u32;
+    let mut bits = 0;
+
+    for c in text.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => break,
+            c if c.is_ascii_whitespace() => continue,
+            _ => return None,
+        };
+
+        n = (n << 6) | u32::from(value);
+        bits += 6;
+
+        if bits >= 8 {
+            bits -= 8;
+            out.push((n >> bits) as u8);
+        }
+    }
+
+    Some(out)
+}
+
+// seconds between 1970-01-01 and the CF epoch, 2001-01-01
+const CF_EPOCH_OFFSET: i64 = 978_307_200;
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> Option<i64> {
+    let year = if month <= 2 {
+        year.checked_sub(1)?
+    } else {
+        year
+    };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era.checked_mul(146_097)?.checked_add(day_of_era - 719_468)
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = if days >= 0 { days } else { days - 146_096 } / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+// the first and last second of the four-digit years, relative to the CF epoch
+const MIN_DATE: f64 = -63_145_526_400.0;
+const MAX_DATE: f64 = 252_423_993_599.0;
+
+/// Formats an absolute time as the ISO 8601 UTC form used by XML plists.
+/// Fractional seconds are truncated, as CoreFoundation does. Times outside
+/// the years 0000 to 9999 are clamped to them, and NaN is taken as the CF
+/// epoch.
+pub(crate) fn format_date(time: f64) -> String {
+    let time = if time.is_nan() {
+        0.0
+    } else {
+        time.clamp(MIN_DATE, MAX_DATE)
+    };
+    let seconds = time.floor() as i64 + CF_EPOCH_OFFSET;
+    let days = seconds.div_euclid(86_400);
+    let second_of_day = seconds.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        second_of_day / 3600,
+        second_of_day / 60 % 60,
+        second_of_day % 60
+    )
+}
+
+fn parse_date(text: &str) -> Option<f64> {
+    let text = text.strip_suffix('Z')?;
+    let (date, time) = match text.find('T') {
+        Some(index) => (&text[..index], &text[index + 1..]),
+        None => (text, "00:00:00"),
+    };
+
+    let mut date = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let year = date.next()??;
+    let month = date.next().unwrap_or(Some(1))?;
+    let day = date.next().unwrap_or(Some(1))?;
+
+    let mut time = time.splitn(3, ':').map(|part| part.parse::<i64>().ok());
+    let hour = time.next()??;
+    let minute = time.next().unwrap_or(Some(0))?;
+    let second = time.next().unwrap_or(Some(0))?;
+
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || !(0..=23).contains(&hour)
+        || !(0..=59).contains(&minute)
+        || !(0..=60).contains(&second)
+    {
+        return None;
+    }
+
+    let seconds = days_from_civil(year, month, day)?
+        .checked_mul(86_400)?
+        .checked_add(hour * 3600 + minute * 60 + second)?
+        .checked_sub(CF_EPOCH_OFFSET)?;
+
+    Some(seconds as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(body: &str) -> String {
+        format!("{}{}\n</plist>\n", HEADER, body)
+    }
+
+    fn round_trip(value: PropertyValue) {
+        assert_eq!(from_xml(&to_xml(&value)), Ok(value));
+    }
+
+    #[test]
+    fn data() {
+        assert_eq!(
+            from_xml(&document("<data>\n\tAAECAwQF\n\t/w==\n</data>")),
+            Ok(PropertyValue::Data(vec![0, 1, 2, 3, 4, 5, 0xff]))
+        );
+        assert_eq!(encode_base64(b"f"), "Zg==");
+        assert_eq!(encode_base64(b"fo"), "Zm8=");
+        assert_eq!(encode_base64(b"foo"), "Zm9v");
+        assert!(from_xml(&document("<data>AA*A</data>")).is_err());
+
+        // long data is split into lines of 68 characters
+        let data = (0..=255).collect::<Vec<u8>>();
+        let xml = to_xml(&PropertyValue::Data(data.clone()));
+        let body = &xml[HEADER.len()..];
+        assert_eq!(body.lines().count(), 9);
+        assert!(body.lines().all(|line| line.len() <= 68));
+        round_trip(PropertyValue::Data(data));
+        round_trip(PropertyValue::Data(Vec::new()));
+    }
+
+    #[test]
+    fn date() {
+        assert_eq!(
+            from_xml(&document("<date>2001-01-01T00:00:00Z</date>")),
+            Ok(PropertyValue::Date(0.0))
+        );
+        assert_eq!(
+            from_xml(&document("<date>1970-01-01T00:00:00Z</date>")),
+            Ok(PropertyValue::Date(-978_307_200.0))
+        );
+        assert_eq!(
+            from_xml(&document("<date>2024-02-29T13:45:30Z</date>")),
+            Ok(PropertyValue::Date(730_907_130.0))
+        );
+        assert_eq!(format_date(730_907_130.75), "2024-02-29T13:45:30Z");
+        assert_eq!(format_date(-1.0), "2000-12-31T23:59:59Z");
+        assert!(from_xml(&document("<date>2024-13-01T00:00:00Z</date>")).is_err());
+        assert!(from_xml(&document("<date>2024-01-01T00:00:00</date>")).is_err());
+        assert!(from_xml(&document("<date>2024-01-01T24:00:00Z</date>")).is_err());
+        assert!(from_xml(&document("<date>2024-01-01T00:60:00Z</date>")).is_err());
+        assert!(from_xml(&document("<date>2024-01-01T00:00:61Z</date>")).is_err());
+        assert!(from_xml(&document(
+            "<date>9223372036854775807-01-01T00:00:00Z</date>"
+        ))
+        .is_err());
+        assert!(from_xml(&document("<date>106751991167300-01-01T00:00:00Z</date>")).is_err());
+
+        assert_eq!(format_date(1e300), "9999-12-31T23:59:59Z");
+        assert_eq!(format_date(f64::NEG_INFINITY), "0000-01-01T00:00:00Z");
+        assert_eq!(format_date(f64::NAN), "2001-01-01T00:00:00Z");
+
+        round_trip(PropertyValue::Date(-978_307_200.0));
+        round_trip(PropertyValue::Date(730_907_130.0));
+    }
+
+    #[test]
+    fn entities() {
+        assert_eq!(
+            from_xml(&document(
+                "<string>&lt;a&gt; &amp; &quot;b&quot; &apos;c&apos; &#x41;&#66;</string>"
+            )),
+            Ok(PropertyValue::String("<a> & \"b\" 'c' AB".to_string()))
+        );
+        assert!(from_xml(&document("<string>&nbsp;</string>")).is_err());
+        assert!(from_xml(&document("<string>&amp</string>")).is_err());
+
+        let xml = to_xml(&PropertyValue::String("<a> & b".to_string()));
+        assert!(xml.contains("<string>&lt;a&gt; &amp; b</string>"));
+        round_trip(PropertyValue::String("<a> & \"b\" 'c' \u{e9}".to_string()));
+
+        let mut map = BTreeMap::new();
+        map.insert("a&b<c>".to_string(), PropertyValue::Bool(true));
+        round_trip(PropertyValue::Dictionary(map));
+    }
+
+    #[test]
+    fn nested_containers() {
+        let xml = document(
+            "<dict>
+                <!-- a comment -->
+                <key>array</key>
+                <array>
+                    <integer>1</integer>
+                    <array/>
+                    <dict>
+                        <key>inner</key>
+                        <false/>
+                    </dict>
+                </array>
+                <key>empty</key>
+                <dict/>
+                <key>string</key>
+                <string/>
+            </dict>",
+        );
+
+        let mut inner = BTreeMap::new();
+        inner.insert("inner".to_string(), PropertyValue::Bool(false));
+        let mut map = BTreeMap::new();
+        map.insert(
+            "array".to_string(),
+            PropertyValue::Array(vec![
+                PropertyValue::Integer(1),
+                PropertyValue::Array(Vec::new()),
+                PropertyValue::Dictionary(inner),
+            ]),
+        );
+        map.insert(
+            "empty".to_string(),
+            PropertyValue::Dictionary(BTreeMap::new()),
+        );
+        map.insert("string".to_string(), PropertyValue::String(String::new()));
+        let value = PropertyValue::Dictionary(map);
+
+        assert_eq!(from_xml(&xml), Ok(value.clone()));
+        round_trip(value);
+
+        assert!(from_xml(&document("<dict><integer>1</integer></dict>")).is_err());
+        assert!(from_xml(&document("<array><true/>")).is_err());
+        assert!(from_xml(&document("<array></dict>")).is_err());
+        assert!(from_xml("<plist><true/></plist><true/>").is_err());
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(parse_integer("0"), Some(0));
+        assert_eq!(parse_integer("+42"), Some(42));
+        assert_eq!(parse_integer("-42"), Some(-42));
+        assert_eq!(parse_integer("0x1F"), Some(31));
+        assert_eq!(parse_integer("0X1f"), Some(31));
+        assert_eq!(parse_integer("9223372036854775807"), Some(i64::MAX));
+        assert_eq!(parse_integer("-9223372036854775808"), Some(i64::MIN));
+        assert_eq!(parse_integer("-9223372036854775809"), None);
+        assert_eq!(parse_integer("18446744073709551615"), Some(-1));
+        assert_eq!(parse_integer("0xffffffffffffffff"), Some(-1));
+        assert_eq!(parse_integer("18446744073709551616"), None);
+        assert_eq!(parse_integer(""), None);
+        assert_eq!(parse_integer("1.5"), None);
+
+        assert_eq!(
+            from_xml(&document("<integer> 7 </integer>")),
+            Ok(PropertyValue::Integer(7))
+        );
+
+        for &value in &[0, 1, -1, i64::MAX, i64::MIN] {
+            round_trip(PropertyValue::Integer(value));
+        }
+    }
+
+    #[test]
+    fn reals() {
+        round_trip(PropertyValue::Float(0.5));
+        round_trip(PropertyValue::Float(-1e300));
+        round_trip(PropertyValue::Float(f64::INFINITY));
+        round_trip(PropertyValue::Float(f64::NEG_INFINITY));
+
+        match from_xml(&document("<real>nan</real>")) {
+            Ok(PropertyValue::Float(value)) => assert!(value.is_nan()),
+            value => panic!("{:?}", value),
+        }
+    }
+
+    #[test]
+    fn errors_report_offsets() {
+        let xml = document("<integer>one</integer>");
+        let error = from_xml(&xml).unwrap_err();
+
+        assert_eq!(error.offset(), xml.find("<integer>").unwrap());
+        assert!(from_xml("").is_err());
+        assert!(from_xml("<plist>").is_err());
+    }
+}
@@ -0,0 +1,706 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::ffi::CStr;
+use std::marker::PhantomData;
+use std::os::raw::c_char;
+#[cfg(target_os = "macos")]
+use std::{
+    mem,
+    os::raw::c_void,
+    ptr,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+#[cfg(target_os = "macos")]
+use core_foundation::{
+    array::CFArray,
+    base::{CFType, TCFType},
+    boolean::CFBoolean,
+    data::CFData,
+    date::CFDate,
+    dictionary::CFDictionary,
+    number::{CFNumber, CFNumberIsFloatType},
+    string::{CFString, CFStringRef},
+};
+#[cfg(target_os = "macos")]
+use io_kit_sys::CFSTR;
+
+#[cfg(target_os = "macos")]
+use crate::error::Error;
+
+/// As in `core_foundation::date`, which only builds for Apple targets.
+pub type CFAbsoluteTime = f64;
+
+/// An owned value of a CF property list, as stored in the I/O Registry.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PropertyValue {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Data(Vec<u8>),
+    /// Seconds relative to the CF epoch, 1 January 2001 00:00:00 GMT.
+    Date(CFAbsoluteTime),
+    Array(Vec<PropertyValue>),
+    Dictionary(BTreeMap<String, PropertyValue>),
+}
+
+impl PropertyValue {
+    /// Converts a CF property list value, returning `None` if `value` (or
+    /// anything nested in it) is not a property list type or a dictionary has
+    /// a non-string key.
+    #[cfg(target_os = "macos")]
+    pub fn from_cf_type(value: &CFType) -> Option<PropertyValue> {
+        if let Some(boolean) = value.downcast::<CFBoolean>() {
+            return Some(PropertyValue::Bool(boolean.into()));
+        }
+
+        if let Some(number) = value.downcast::<CFNumber>() {
+            let is_float = unsafe { CFNumberIsFloatType(number.as_concrete_TypeRef()) != 0 };
+
+            return if is_float {
+                number.to_f64().map(PropertyValue::Float)
+            } else {
+                number.to_i64().map(PropertyValue::Integer)
+            };
+        }
+
+        if let Some(string) = value.downcast::<CFString>() {
+            return Some(PropertyValue::String(string.to_string()));
+        }
+
+        if let Some(data) = value.downcast::<CFData>() {
+            return Some(PropertyValue::Data(data.bytes().to_vec()));
+        }
+
+        if let Some(date) = value.downcast::<CFDate>() {
+            return Some(PropertyValue::Date(date.abs_time()));
+        }
+
+        if let Some(array) = value.downcast::<CFArray>() {
+            let mut values = Vec::with_capacity(array.len() as usize);
+
+            for item in array.iter() {
+                let item = unsafe { CFType::wrap_under_get_rule(*item) };
+                values.push(PropertyValue::from_cf_type(&item)?);
+            }
+
+            return Some(PropertyValue::Array(values));
+        }
+
+        if let Some(dictionary) = value.downcast::<CFDictionary>() {
+            let (keys, values) = dictionary.get_keys_and_values();
+            let mut map = BTreeMap::new();
+
+            for (key, value) in keys.into_iter().zip(values) {
+                let key = unsafe { CFType::wrap_under_get_rule(key) };
+                let value = unsafe { CFType::wrap_under_get_rule(value) };
+
+                map.insert(
+                    key.downcast::<CFString>()?.to_string(),
+                    PropertyValue::from_cf_type(&value)?,
+                );
+            }
+
+            return Some(PropertyValue::Dictionary(map));
+        }
+
+        None
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn to_cf_type(&self) -> CFType {
+        match *self {
+            PropertyValue::Bool(value) => CFBoolean::from(value).as_CFType(),
+            PropertyValue::Integer(value) => CFNumber::from(value).as_CFType(),
+            PropertyValue::Float(value) => CFNumber::from(value).as_CFType(),
+            PropertyValue::String(ref value) => CFString::new(value).as_CFType(),
+            PropertyValue::Data(ref value) => CFData::from_buffer(value).as_CFType(),
+            PropertyValue::Date(value) => CFDate::new(value).as_CFType(),
+            PropertyValue::Array(ref values) => {
+                let values: Vec<CFType> = values.iter().map(PropertyValue::to_cf_type).collect();
+
+                CFArray::from_CFTypes(&values).as_CFType()
+            }
+            PropertyValue::Dictionary(ref map) => {
+                let pairs: Vec<(CFString, CFType)> = map
+                    .iter()
+                    .map(|(key, value)| (CFString::new(key), value.to_cf_type()))
+                    .collect();
+
+                CFDictionary::from_CFType_pairs(&pairs).as_CFType()
+            }
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            PropertyValue::Bool(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            PropertyValue::Integer(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the integer as an `u64`, reinterpreting the sign bit the way
+    /// IOKit stores unsigned 64-bit numbers.
+    pub fn as_u64(&self) -> Option<u64> {
+        self.as_i64().map(|value| value as u64)
+    }
+
+    pub fn as_u32(&self) -> Option<u32> {
+        self.as_i64().and_then(|value| {
+            if value >= 0 && value <= u32::MAX as i64 {
+                Some(value as u32)
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            PropertyValue::Float(value) => Some(value),
+            PropertyValue::Integer(value) => Some(value as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            PropertyValue::String(ref value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_data(&self) -> Option<&[u8]> {
+        match *self {
+            PropertyValue::Data(ref value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[PropertyValue]> {
+        match *self {
+            PropertyValue::Array(ref values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_dictionary(&self) -> Option<&BTreeMap<String, PropertyValue>> {
+        match *self {
+            PropertyValue::Dictionary(ref map) => Some(map),
+            _ => None,
+        }
+    }
+}
+
+impl From<bool> for PropertyValue {
+    fn from(value: bool) -> PropertyValue {
+        PropertyValue::Bool(value)
+    }
+}
+
+impl From<i32> for PropertyValue {
+    fn from(value: i32) -> PropertyValue {
+        PropertyValue::Integer(value as i64)
+    }
+}
+
+impl From<i64> for PropertyValue {
+    fn from(value: i64) -> PropertyValue {
+        PropertyValue::Integer(value)
+    }
+}
+
+impl From<u32> for PropertyValue {
+    fn from(value: u32) -> PropertyValue {
+        PropertyValue::Integer(value as i64)
+    }
+}
+
+impl From<u64> for PropertyValue {
+    fn from(value: u64) -> PropertyValue {
+        PropertyValue::Integer(value as i64)
+    }
+}
+
+impl From<f64> for PropertyValue {
+    fn from(value: f64) -> PropertyValue {
+        PropertyValue::Float(value)
+    }
+}
+
+impl<'a> From<&'a str> for PropertyValue {
+    fn from(value: &'a str) -> PropertyValue {
+        PropertyValue::String(value.to_string())
+    }
+}
+
+impl From<String> for PropertyValue {
+    fn from(value: String) -> PropertyValue {
+        PropertyValue::String(value)
+    }
+}
+
+impl From<Vec<u8>> for PropertyValue {
+    fn from(value: Vec<u8>) -> PropertyValue {
+        PropertyValue::Data(value)
+    }
+}
+
+impl From<Vec<PropertyValue>> for PropertyValue {
+    fn from(values: Vec<PropertyValue>) -> PropertyValue {
+        PropertyValue::Array(values)
+    }
+}
+
+impl From<BTreeMap<String, PropertyValue>> for PropertyValue {
+    fn from(map: BTreeMap<String, PropertyValue>) -> PropertyValue {
+        PropertyValue::Dictionary(map)
+    }
+}
+
+/// A Rust type that property values convert to and from.
+pub trait PropertyType: Sized {
+    fn from_property_value(value: PropertyValue) -> Option<Self>;
+
+    fn into_property_value(self) -> PropertyValue;
+}
+
+impl PropertyType for PropertyValue {
+    fn from_property_value(value: PropertyValue) -> Option<PropertyValue> {
+        Some(value)
+    }
+
+    fn into_property_value(self) -> PropertyValue {
+        self
+    }
+}
+
+impl PropertyType for bool {
+    fn from_property_value(value: PropertyValue) -> Option<bool> {
+        value.as_bool()
+    }
+
+    fn into_property_value(self) -> PropertyValue {
+        PropertyValue::Bool(self)
+    }
+}
+
+macro_rules! impl_integer_property_type {
+    ($($ty:ty),*) => {
+        $(
+            impl PropertyType for $ty {
+                fn from_property_value(value: PropertyValue) -> Option<$ty> {
+                    value.as_i64().and_then(|value| <$ty>::try_from(value).ok())
+                }
+
+                fn into_property_value(self) -> PropertyValue {
+                    PropertyValue::Integer(self as i64)
+                }
+            }
+        )*
+    };
+}
+
+impl_integer_property_type!(u8, u16, u32, i8, i16, i32, i64);
+
+impl PropertyType for u64 {
+    fn from_property_value(value: PropertyValue) -> Option<u64> {
+        value.as_u64()
+    }
+
+    fn into_property_value(self) -> PropertyValue {
+        PropertyValue::from(self)
+    }
+}
+
+impl PropertyType for f64 {
+    fn from_property_value(value: PropertyValue) -> Option<f64> {
+        value.as_f64()
+    }
+
+    fn into_property_value(self) -> PropertyValue {
+        PropertyValue::Float(self)
+    }
+}
+
+impl PropertyType for String {
+    fn from_property_value(value: PropertyValue) -> Option<String> {
+        match value {
+            PropertyValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn into_property_value(self) -> PropertyValue {
+        PropertyValue::String(self)
+    }
+}
+
+impl PropertyType for Vec<u8> {
+    fn from_property_value(value: PropertyValue) -> Option<Vec<u8>> {
+        match value {
+            PropertyValue::Data(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn into_property_value(self) -> PropertyValue {
+        PropertyValue::Data(self)
+    }
+}
+
+impl PropertyType for Vec<PropertyValue> {
+    fn from_property_value(value: PropertyValue) -> Option<Vec<PropertyValue>> {
+        match value {
+            PropertyValue::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    fn into_property_value(self) -> PropertyValue {
+        PropertyValue::Array(self)
+    }
+}
+
+impl PropertyType for BTreeMap<String, PropertyValue> {
+    fn from_property_value(value: PropertyValue) -> Option<BTreeMap<String, PropertyValue>> {
+        match value {
+            PropertyValue::Dictionary(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    fn into_property_value(self) -> PropertyValue {
+        PropertyValue::Dictionary(self)
+    }
+}
+
+/// The name of a property together with the type of its value. The
+/// `CFString` for the name is created on first use and kept for the life of
+/// the process, so keys are meant to be `static`s, as in `crate::keys::typed`.
+pub struct PropertyKey<T> {
+    name: &'static str,
+    #[cfg(target_os = "macos")]
+    cf_string: AtomicPtr<c_void>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> PropertyKey<T> {
+    pub const fn new(name: &'static str) -> PropertyKey<T> {
+        PropertyKey {
+            name,
+            #[cfg(target_os = "macos")]
+            cf_string: AtomicPtr::new(ptr::null_mut()),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Takes the name from a raw key such as those in `io_kit_sys`.
+    ///
+    /// # Safety
+    ///
+    /// `name` must point to a nul-terminated string that lives for the rest
+    /// of the process.
+    ///
+    /// # Panics
+    ///
+    /// Panics, or fails to compile in a `static`, if the name is not UTF-8.
+    pub const unsafe fn from_raw(name: *const c_char) -> PropertyKey<T> {
+        match CStr::from_ptr(name).to_str() {
+            Ok(name) => PropertyKey::new(name),
+            Err(_) => panic!("property key names must be UTF-8"),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn to_cf_string(&self) -> CFString {
+        let mut string = self.cf_string.load(Ordering::Acquire);
+
+        if string.is_null() {
+            let created = CFString::new(self.name);
+            let created_ref = created.as_concrete_TypeRef() as *mut c_void;
+
+            // the winner's reference is leaked into the cache
+            string = match self.cf_string.compare_exchange(
+                ptr::null_mut(),
+                created_ref,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    mem::forget(created);
+                    created_ref
+                }
+                Err(cached) => cached,
+            };
+        }
+
+        unsafe { TCFType::wrap_under_get_rule(string as CFStringRef) }
+    }
+}
+
+/// Typed access to the properties of registry entries and devices.
+#[cfg(target_os = "macos")]
+pub trait TIOProperties {
+    /// Returns the raw value stored under `key`.
+    fn copy_cf_property(&self, key: &CFString) -> Option<CFType>;
+
+    /// Stores a raw value under `key`.
+    fn set_cf_property(&self, key: &CFString, value: &CFType) -> Result<(), Error>;
+
+    fn copy_property(&self, key: *const c_char) -> Option<CFType> {
+        self.copy_cf_property(&unsafe { TCFType::wrap_under_get_rule(CFSTR(key)) })
+    }
+
+    fn get<T: PropertyType>(&self, key: &PropertyKey<T>) -> Option<T> {
+        self.copy_cf_property(&key.to_cf_string())
+            .and_then(|value| PropertyValue::from_cf_type(&value))
+            .and_then(T::from_property_value)
+    }
+
+    fn set<T: PropertyType>(&self, key: &PropertyKey<T>, value: T) -> Result<(), Error> {
+        self.set_cf_property(
+            &key.to_cf_string(),
+            &value.into_property_value().to_cf_type(),
+        )
+    }
+
+    fn get_value(&self, key: *const c_char) -> Option<PropertyValue> {
+        self.copy_property(key)
+            .and_then(|value| PropertyValue::from_cf_type(&value))
+    }
+
+    fn get_bool(&self, key: *const c_char) -> Option<bool> {
+        self.get_value(key).and_then(|value| value.as_bool())
+    }
+
+    fn get_i64(&self, key: *const c_char) -> Option<i64> {
+        self.get_value(key).and_then(|value| value.as_i64())
+    }
+
+    fn get_u64(&self, key: *const c_char) -> Option<u64> {
+        self.get_value(key).and_then(|value| value.as_u64())
+    }
+
+    fn get_u32(&self, key: *const c_char) -> Option<u32> {
+        self.get_value(key).and_then(|value| value.as_u32())
+    }
+
+    fn get_f64(&self, key: *const c_char) -> Option<f64> {
+        self.get_value(key).and_then(|value| value.as_f64())
+    }
+
+    fn get_string(&self, key: *const c_char) -> Option<String> {
+        match self.get_value(key) {
+            Some(PropertyValue::String(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn get_data(&self, key: *const c_char) -> Option<Vec<u8>> {
+        match self.get_value(key) {
+            Some(PropertyValue::Data(value)) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn property_types_reject_wrong_types() {
+        assert_eq!(bool::from_property_value(PropertyValue::Integer(1)), None);
+        assert_eq!(
+            u32::from_property_value(PropertyValue::String("1".into())),
+            None
+        );
+        assert_eq!(u32::from_property_value(PropertyValue::Integer(-1)), None);
+        assert_eq!(u8::from_property_value(PropertyValue::Integer(256)), None);
+        assert_eq!(i8::from_property_value(PropertyValue::Integer(-129)), None);
+        assert_eq!(
+            String::from_property_value(PropertyValue::Data(vec![])),
+            None
+        );
+        assert_eq!(
+            Vec::<u8>::from_property_value(PropertyValue::Array(vec![])),
+            None
+        );
+        assert_eq!(
+            Vec::<PropertyValue>::from_property_value(PropertyValue::Dictionary(BTreeMap::new())),
+            None
+        );
+        assert_eq!(
+            BTreeMap::from_property_value(PropertyValue::Array(vec![])),
+            None
+        );
+        assert_eq!(f64::from_property_value(PropertyValue::Bool(true)), None);
+    }
+
+    #[test]
+    fn accessors() {
+        assert_eq!(PropertyValue::Integer(-1).as_u64(), Some(u64::MAX));
+        assert_eq!(PropertyValue::Integer(-1).as_u32(), None);
+        assert_eq!(PropertyValue::Integer(1 << 32).as_u32(), None);
+        assert_eq!(PropertyValue::Integer(7).as_f64(), Some(7.0));
+        assert_eq!(PropertyValue::Float(7.0).as_i64(), None);
+        assert_eq!(PropertyValue::from("x").as_str(), Some("x"));
+        assert_eq!(PropertyValue::Bool(true).as_str(), None);
+        assert_eq!(PropertyValue::Data(vec![1]).as_data(), Some(&[1u8][..]));
+        assert_eq!(PropertyValue::Array(vec![]).as_dictionary(), None);
+    }
+
+    // CoreFoundation is only available on macOS
+    #[cfg(target_os = "macos")]
+    mod cf {
+        use std::cell::RefCell;
+        use std::ffi::CString;
+
+        use super::*;
+
+        fn dictionary() -> BTreeMap<String, PropertyValue> {
+            let mut map = BTreeMap::new();
+            map.insert("flag".to_string(), PropertyValue::Bool(true));
+            map.insert("count".to_string(), PropertyValue::Integer(-3));
+            map.insert(
+                "nested".to_string(),
+                PropertyValue::Array(vec![
+                    PropertyValue::String("a".to_string()),
+                    PropertyValue::Data(vec![0, 1, 0xff]),
+                ]),
+            );
+            map
+        }
+
+        fn values() -> Vec<PropertyValue> {
+            vec![
+                PropertyValue::Bool(false),
+                PropertyValue::Bool(true),
+                PropertyValue::Integer(0),
+                PropertyValue::Integer(-1),
+                PropertyValue::Integer(i64::from(u32::MAX)),
+                PropertyValue::Integer(i64::MIN),
+                PropertyValue::Integer(i64::MAX),
+                PropertyValue::Float(-1.5),
+                PropertyValue::Float(2.0),
+                PropertyValue::Float(f64::MAX),
+                PropertyValue::Float(f64::MIN_POSITIVE),
+                PropertyValue::String("IOService".to_string()),
+                PropertyValue::String(String::new()),
+                PropertyValue::Data(vec![]),
+                PropertyValue::Data(vec![0xde, 0xad, 0xbe, 0xef]),
+                PropertyValue::Date(123_456.5),
+                PropertyValue::Date(-978_307_200.0),
+                PropertyValue::Array(vec![]),
+                PropertyValue::Array(vec![
+                    PropertyValue::Array(vec![PropertyValue::Integer(1)]),
+                    PropertyValue::Dictionary(dictionary()),
+                ]),
+                PropertyValue::Dictionary(BTreeMap::new()),
+                PropertyValue::Dictionary(dictionary()),
+            ]
+        }
+
+        #[derive(Default)]
+        struct Properties(RefCell<BTreeMap<String, CFType>>);
+
+        impl TIOProperties for Properties {
+            fn copy_cf_property(&self, key: &CFString) -> Option<CFType> {
+                self.0.borrow().get(&key.to_string()).cloned()
+            }
+
+            fn set_cf_property(&self, key: &CFString, value: &CFType) -> Result<(), Error> {
+                self.0.borrow_mut().insert(key.to_string(), value.clone());
+                Ok(())
+            }
+        }
+
+        fn typed_round_trip<T>(value: T)
+        where
+            T: PropertyType + Clone + PartialEq + std::fmt::Debug,
+        {
+            let cf = value.clone().into_property_value().to_cf_type();
+
+            assert_eq!(
+                PropertyValue::from_cf_type(&cf).and_then(T::from_property_value),
+                Some(value)
+            );
+        }
+
+        static COUNT: PropertyKey<u32> = PropertyKey::new("Count");
+        static NAME: PropertyKey<String> = PropertyKey::new("Name");
+
+        #[test]
+        fn cf_types_round_trip() {
+            for value in values() {
+                assert_eq!(
+                    PropertyValue::from_cf_type(&value.to_cf_type()),
+                    Some(value)
+                );
+            }
+
+            typed_round_trip(true);
+            typed_round_trip(u8::MAX);
+            typed_round_trip(u16::MAX);
+            typed_round_trip(u32::MAX);
+            typed_round_trip(u64::MAX);
+            typed_round_trip(i8::MIN);
+            typed_round_trip(i64::MIN);
+            typed_round_trip(2.25f64);
+            typed_round_trip("IOMedia".to_string());
+            typed_round_trip(vec![1u8, 2]);
+            typed_round_trip(dictionary());
+        }
+
+        #[test]
+        fn non_property_list_types_are_rejected() {
+            let run_loop = core_foundation::runloop::CFRunLoop::get_current();
+
+            assert_eq!(PropertyValue::from_cf_type(&run_loop.as_CFType()), None);
+        }
+
+        #[test]
+        fn typed_getters() {
+            let properties = Properties::default();
+            let count = CString::new("Count").unwrap();
+            let name = CString::new("Name").unwrap();
+            let missing = CString::new("Missing").unwrap();
+
+            properties.set(&COUNT, 42).unwrap();
+            properties.set(&NAME, "disk0".to_string()).unwrap();
+
+            assert_eq!(properties.get(&COUNT), Some(42));
+            assert_eq!(properties.get(&NAME), Some("disk0".to_string()));
+            assert_eq!(properties.get_u32(count.as_ptr()), Some(42));
+            assert_eq!(properties.get_i64(count.as_ptr()), Some(42));
+            assert_eq!(properties.get_f64(count.as_ptr()), Some(42.0));
+            assert_eq!(
+                properties.get_string(name.as_ptr()),
+                Some("disk0".to_string())
+            );
+
+            // wrong types
+            assert_eq!(properties.get_string(count.as_ptr()), None);
+            assert_eq!(properties.get_bool(count.as_ptr()), None);
+            assert_eq!(properties.get_u32(name.as_ptr()), None);
+            assert_eq!(properties.get_data(name.as_ptr()), None);
+
+            // missing keys
+            assert_eq!(properties.get_u32(missing.as_ptr()), None);
+            assert_eq!(properties.get_value(missing.as_ptr()), None);
+        }
+    }
+}
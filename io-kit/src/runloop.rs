@@ -0,0 +1,240 @@
+//! A `CFRunLoop` on a background thread, shared by everything in the crate
+//! that needs notifications delivered without the caller running a run loop.
+//! `HidEventLoop` runs its own.
+
+use std::collections::VecDeque;
+use std::os::raw::c_void;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread::{self, JoinHandle, ThreadId};
+
+use core_foundation::base::TCFType;
+use core_foundation::runloop::{
+    kCFRunLoopDefaultMode, CFRunLoop, CFRunLoopSource, CFRunLoopSourceContext,
+    CFRunLoopSourceCreate, CFRunLoopSourceInvalidate, CFRunLoopSourceRef, CFRunLoopSourceSignal,
+    CFRunLoopWakeUp,
+};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+static BACKGROUND_RUN_LOOP: OnceLock<BackgroundRunLoop> = OnceLock::new();
+
+pub(crate) struct BackgroundRunLoop {
+    run_loop: CFRunLoop,
+    // signalled to run the queued jobs on the run loop thread
+    source: AssertSend<CFRunLoopSourceRef>,
+    jobs: Arc<Mutex<VecDeque<Job>>>,
+    thread: ThreadId,
+    running: Arc<AtomicBool>,
+    handle: Mutex<Option<JoinHandle<()>>>,
+}
+
+unsafe impl Send for BackgroundRunLoop {}
+unsafe impl Sync for BackgroundRunLoop {}
+
+impl BackgroundRunLoop {
+    /// Returns the shared run loop, starting its thread on first use.
+    pub(crate) fn get() -> &'static BackgroundRunLoop {
+        BACKGROUND_RUN_LOOP.get_or_init(|| BackgroundRunLoop::spawn("io-kit-run-loop"))
+    }
+
+    /// Starts a run loop on a new thread, which runs until `stop` is called.
+    pub(crate) fn spawn(name: &str) -> BackgroundRunLoop {
+        let (sender, receiver) = mpsc::channel();
+        let jobs = Arc::new(Mutex::new(VecDeque::<Job>::new()));
+        let running = Arc::new(AtomicBool::new(true));
+        let thread_jobs = jobs.clone();
+        let thread_running = running.clone();
+
+        let handle = thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || unsafe {
+                let mut context = CFRunLoopSourceContext {
+                    version: 0,
+                    info: Arc::as_ptr(&thread_jobs) as *mut c_void,
+                    retain: None,
+                    release: None,
+                    copyDescription: None,
+                    equal: None,
+                    hash: None,
+                    schedule: None,
+                    cancel: None,
+                    perform: perform_jobs,
+                };
+                let source: CFRunLoopSource = TCFType::wrap_under_create_rule(
+                    CFRunLoopSourceCreate(ptr::null(), 0, &mut context),
+                );
+                let run_loop = CFRunLoop::get_current();
+
+                run_loop.add_source(&source, kCFRunLoopDefaultMode);
+
+                sender
+                    .send((
+                        run_loop,
+                        AssertSend(source.as_concrete_TypeRef()),
+                        thread::current().id(),
+                    ))
+                    .unwrap();
+
+                // the job source keeps the run loop from ever running out of sources
+                while thread_running.load(Ordering::SeqCst) {
+                    CFRunLoop::run_current();
+                }
+
+                // `thread_jobs` outlives the source that points at it
+                CFRunLoopSourceInvalidate(source.as_concrete_TypeRef());
+            })
+            .expect("failed to spawn the IOKit run loop thread");
+
+        let (run_loop, source, thread) = receiver.recv().unwrap();
+
+        BackgroundRunLoop {
+            run_loop,
+            source,
+            jobs,
+            thread,
+            running,
+            handle: Mutex::new(Some(handle)),
+        }
+    }
+
+    /// Stops the run loop once the jobs queued so far have run, and waits for
+    /// its thread to exit. Must not be called from that thread.
+    pub(crate) fn stop(&self) {
+        let handle = match self.handle.lock().unwrap().take() {
+            Some(handle) => handle,
+            None => return,
+        };
+        let running = self.running.clone();
+
+        self.run(move || {
+            running.store(false, Ordering::SeqCst);
+            CFRunLoop::get_current().stop();
+        });
+
+        let _ = handle.join();
+
+        // fails the callers of any job that can no longer run
+        self.jobs.lock().unwrap().clear();
+    }
+
+    /// Runs `job` on the run loop thread and waits for its result. Runs it
+    /// directly when called from that thread.
+    pub(crate) fn run<F, R>(&self, job: F) -> R
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        self.try_run(job)
+            .expect("job panicked on the IOKit run loop thread, or it was stopped")
+    }
+
+    /// Like `run`, but returns `None` instead of panicking when `job` panics
+    /// on the run loop thread or the run loop has been stopped.
+    pub(crate) fn try_run<F, R>(&self, job: F) -> Option<R>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        if thread::current().id() == self.thread {
+            return Some(job());
+        }
+
+        let (sender, receiver) = mpsc::channel();
+
+        {
+            // checked under the lock, so a job is either queued before the
+            // run loop stops, and run, or not queued at all
+            let mut jobs = self.jobs.lock().unwrap();
+
+            if !self.running.load(Ordering::SeqCst) {
+                return None;
+            }
+
+            jobs.push_back(Box::new(move || {
+                let _ = sender.send(job());
+            }));
+        }
+
+        unsafe {
+            CFRunLoopSourceSignal(self.source.0);
+            CFRunLoopWakeUp(self.run_loop.as_concrete_TypeRef());
+        }
+
+        receiver.recv().ok()
+    }
+
+    /// Creates a value that lives on the run loop thread: `create` runs there,
+    /// and the value is dropped there too.
+    pub(crate) fn create<F, T, E>(&self, create: F) -> Result<RunLoopBound<T>, E>
+    where
+        F: FnOnce(&CFRunLoop) -> Result<T, E> + Send + 'static,
+        T: 'static,
+        E: Send + 'static,
+    {
+        self.run(move || {
+            let run_loop = CFRunLoop::get_current();
+
+            create(&run_loop).map(|value| AssertSend(RunLoopBound { value: Some(value) }))
+        })
+        .map(|bound| bound.0)
+    }
+}
+
+extern "C" fn perform_jobs(info: *const c_void) {
+    let jobs = unsafe { &*(info as *const Mutex<VecDeque<Job>>) };
+
+    loop {
+        let job = jobs.lock().unwrap().pop_front();
+
+        match job {
+            Some(job) => {
+                let _ = panic::catch_unwind(AssertUnwindSafe(job));
+            }
+            None => break,
+        }
+    }
+}
+
+/// Moves a value across threads that is only ever used on one of them at a time.
+pub(crate) struct AssertSend<T>(pub(crate) T);
+
+unsafe impl<T> Send for AssertSend<T> {}
+
+/// A value created by `BackgroundRunLoop::create`, which is only touched on
+/// the run loop thread.
+pub(crate) struct RunLoopBound<T: 'static> {
+    value: Option<T>,
+}
+
+unsafe impl<T> Send for RunLoopBound<T> {}
+
+impl<T> RunLoopBound<T> {
+    /// Runs `f` with the value on the run loop thread.
+    pub(crate) fn with<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        // `run` returns only once `f` is done with the borrow
+        let value = AssertSend(self.value.as_ref().unwrap() as *const T);
+
+        BackgroundRunLoop::get().run(move || {
+            let value = value;
+            f(unsafe { &*value.0 })
+        })
+    }
+}
+
+impl<T> Drop for RunLoopBound<T> {
+    fn drop(&mut self) {
+        if let Some(value) = self.value.take() {
+            let value = AssertSend(value);
+
+            BackgroundRunLoop::get().run(move || drop(value));
+        }
+    }
+}
@@ -0,0 +1,451 @@
+//! Offline copies of the I/O Registry.
+//!
+//! A `RegistrySnapshot` holds one tree per registry plane and is plain Rust
+//! data: only `capture`, which exists on macOS alone, talks to IOKit, so
+//! snapshots saved on one machine can be loaded and queried anywhere. The XML
+//! form is the one written by `ioreg -a -l -p <plane>`.
+//!
+//! `ioreg` does not record the class hierarchy. `capture` records it for every
+//! class it sees and `to_xml` saves it under an extra `IOClassHierarchy` key
+//! of the root entry, so snapshots reloaded from their own XML keep it; those
+//! loaded from plain `ioreg` output only know the superclasses added with
+//! `insert_superclass`.
+
+use std::collections::BTreeMap;
+
+#[cfg(target_os = "macos")]
+use core_foundation::base::{CFType, TCFType};
+#[cfg(target_os = "macos")]
+use core_foundation::string::CFString;
+#[cfg(target_os = "macos")]
+use io_kit_sys::{IOObjectCopySuperclassForClass, IOServiceGetBusyStateAndTime};
+#[cfg(target_os = "macos")]
+use mach::kern_return::KERN_SUCCESS;
+
+#[cfg(target_os = "macos")]
+use crate::base::{IORegistryEntry, IORegistryTraversal, IORegistryWalker, TIOObject};
+#[cfg(target_os = "macos")]
+use crate::error::Error;
+use crate::matching::{self, Matching};
+use crate::plane::IORegistryPlane;
+use crate::plist::{self, PlistError};
+use crate::property::PropertyValue;
+
+// keys `ioreg -a` adds to the properties of every entry
+const NAME_KEY: &str = "IORegistryEntryName";
+const CLASS_KEY: &str = "IOObjectClass";
+const ID_KEY: &str = "IORegistryEntryID";
+const LOCATION_KEY: &str = "IORegistryEntryLocation";
+const RETAIN_COUNT_KEY: &str = "IOObjectRetainCount";
+const BUSY_STATE_KEY: &str = "IOServiceBusyState";
+const STATE_KEY: &str = "IOServiceState";
+#[cfg(target_os = "macos")]
+const BUSY_TIME_KEY: &str = "IOServiceBusyTime";
+const CHILDREN_KEY: &str = "IORegistryEntryChildren";
+// class name -> superclass name, only written by `RegistrySnapshot::to_xml`
+const CLASS_HIERARCHY_KEY: &str = "IOClassHierarchy";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SnapshotEntry {
+    pub name: String,
+    pub class: String,
+    pub id: u64,
+    pub location: Option<String>,
+    pub retain_count: Option<u32>,
+    pub busy_state: Option<u32>,
+    /// The `IOService` state bits, when the snapshot came from `ioreg`.
+    pub state: Option<u64>,
+    pub properties: BTreeMap<String, PropertyValue>,
+    pub children: Vec<SnapshotEntry>,
+}
+
+impl SnapshotEntry {
+    /// Copies a single live entry, without its children.
+    #[cfg(target_os = "macos")]
+    pub fn capture(
+        entry: &IORegistryEntry,
+        plane: IORegistryPlane,
+    ) -> Result<SnapshotEntry, Error> {
+        let mut properties = BTreeMap::new();
+
+        // fails for entries that are not services
+        let mut state = 0;
+        let mut busy_state = 0;
+        let mut busy_time = 0;
+        let result = unsafe {
+            IOServiceGetBusyStateAndTime(
+                entry.as_io_object_t(),
+                &mut state,
+                &mut busy_state,
+                &mut busy_time,
+            )
+        };
+        let (state, busy_state) = if result == KERN_SUCCESS {
+            properties.insert(BUSY_TIME_KEY.to_string(), busy_time.into());
+            (Some(state), Some(busy_state))
+        } else {
+            (None, None)
+        };
+
+        let cf_properties = entry.create_cf_properties()?;
+        let (keys, values) = cf_properties.get_keys_and_values();
+
+        for (key, value) in keys.into_iter().zip(values) {
+            let key = unsafe { CFString::wrap_under_get_rule(key as _) };
+            let value = unsafe { CFType::wrap_under_get_rule(value) };
+
+            if let Some(value) = PropertyValue::from_cf_type(&value) {
+                properties.insert(key.to_string(), value);
+            }
+        }
+
+        Ok(SnapshotEntry {
+            name: entry
+                .get_name_in_plane(plane)
+                .or_else(|_| entry.get_name())?,
+            class: entry.get_class()?,
+            id: entry.get_registry_entry_id()?,
+            location: entry
+                .get_location_in_plane(plane)
+                .ok()
+                .filter(|location| !location.is_empty()),
+            retain_count: Some(entry.get_retain_count()),
+            busy_state,
+            state,
+            properties,
+            children: Vec::new(),
+        })
+    }
+
+    /// Copies a live entry together with everything below it in `plane`.
+    #[cfg(target_os = "macos")]
+    pub fn capture_tree(
+        entry: &IORegistryEntry,
+        plane: IORegistryPlane,
+    ) -> Result<SnapshotEntry, Error> {
+        let walker = entry.walk(
+            plane,
+            io_kit_sys::kIORegistryIterateRecursively,
+            IORegistryTraversal::DepthFirst,
+        )?;
+
+        SnapshotEntry::build_tree(SnapshotEntry::capture(entry, plane)?, walker, plane)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn build_tree(
+        root: SnapshotEntry,
+        walker: IORegistryWalker,
+        plane: IORegistryPlane,
+    ) -> Result<SnapshotEntry, Error> {
+        // `stack[d]` is the open entry at depth `d`
+        let mut stack = vec![root];
+
+        for (depth, entry) in walker {
+            while stack.len() > depth {
+                let child = stack.pop().unwrap();
+                stack.last_mut().unwrap().children.push(child);
+            }
+
+            stack.push(SnapshotEntry::capture(&entry, plane)?);
+        }
+
+        while stack.len() > 1 {
+            let child = stack.pop().unwrap();
+            stack.last_mut().unwrap().children.push(child);
+        }
+
+        Ok(stack.pop().unwrap())
+    }
+
+    /// Converts an entry dictionary as written by `ioreg -a`.
+    pub fn from_property_value(value: &PropertyValue) -> Option<SnapshotEntry> {
+        let mut properties = value.as_dictionary()?.clone();
+
+        let mut take_string = |key: &str| match properties.remove(key) {
+            Some(PropertyValue::String(value)) => Some(value),
+            _ => None,
+        };
+        let name = take_string(NAME_KEY)?;
+        let class = take_string(CLASS_KEY)?;
+        let location = take_string(LOCATION_KEY);
+
+        let id = properties.remove(ID_KEY)?.as_u64()?;
+        let retain_count = properties
+            .remove(RETAIN_COUNT_KEY)
+            .and_then(|value| value.as_u32());
+        let busy_state = properties
+            .remove(BUSY_STATE_KEY)
+            .and_then(|value| value.as_u32());
+        let state = properties
+            .remove(STATE_KEY)
+            .and_then(|value| value.as_u64());
+
+        let children = match properties.remove(CHILDREN_KEY) {
+            Some(PropertyValue::Array(children)) => children
+                .iter()
+                .map(SnapshotEntry::from_property_value)
+                .collect::<Option<Vec<_>>>()?,
+            Some(_) => return None,
+            None => Vec::new(),
+        };
+
+        Some(SnapshotEntry {
+            name,
+            class,
+            id,
+            location,
+            retain_count,
+            busy_state,
+            state,
+            properties,
+            children,
+        })
+    }
+
+    /// Converts the entry and its children into the dictionary layout of
+    /// `ioreg -a`.
+    pub fn to_property_value(&self) -> PropertyValue {
+        let mut map = self.properties.clone();
+
+        map.insert(NAME_KEY.to_string(), self.name.clone().into());
+        map.insert(CLASS_KEY.to_string(), self.class.clone().into());
+        map.insert(ID_KEY.to_string(), self.id.into());
+
+        if let Some(ref location) = self.location {
+            map.insert(LOCATION_KEY.to_string(), location.clone().into());
+        }
+        if let Some(retain_count) = self.retain_count {
+            map.insert(RETAIN_COUNT_KEY.to_string(), retain_count.into());
+        }
+        if let Some(busy_state) = self.busy_state {
+            map.insert(BUSY_STATE_KEY.to_string(), busy_state.into());
+        }
+        if let Some(state) = self.state {
+            map.insert(STATE_KEY.to_string(), state.into());
+        }
+        if !self.children.is_empty() {
+            let children = self
+                .children
+                .iter()
+                .map(SnapshotEntry::to_property_value)
+                .collect::<Vec<_>>();
+
+            map.insert(CHILDREN_KEY.to_string(), children.into());
+        }
+
+        PropertyValue::Dictionary(map)
+    }
+
+    /// Iterates depth-first over this entry (at depth 0) and its descendants.
+    pub fn walk(&self) -> SnapshotWalk<'_> {
+        SnapshotWalk {
+            stack: vec![(0, self)],
+        }
+    }
+
+    pub fn find<P>(&self, mut predicate: P) -> Option<&SnapshotEntry>
+    where
+        P: FnMut(&SnapshotEntry) -> bool,
+    {
+        self.walk()
+            .map(|(_, entry)| entry)
+            .find(|entry| predicate(entry))
+    }
+
+    pub fn find_by_id(&self, id: u64) -> Option<&SnapshotEntry> {
+        self.find(|entry| entry.id == id)
+    }
+}
+
+pub struct SnapshotWalk<'a> {
+    stack: Vec<(usize, &'a SnapshotEntry)>,
+}
+
+impl<'a> Iterator for SnapshotWalk<'a> {
+    type Item = (usize, &'a SnapshotEntry);
+
+    fn next(&mut self) -> Option<(usize, &'a SnapshotEntry)> {
+        let (depth, entry) = self.stack.pop()?;
+
+        for child in entry.children.iter().rev() {
+            self.stack.push((depth + 1, child));
+        }
+
+        Some((depth, entry))
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RegistrySnapshot {
+    planes: BTreeMap<IORegistryPlane, SnapshotEntry>,
+    // class name -> superclass name
+    superclasses: BTreeMap<String, String>,
+}
+
+impl RegistrySnapshot {
+    pub fn new() -> RegistrySnapshot {
+        RegistrySnapshot::default()
+    }
+
+    /// Copies the live registry tree of each of `planes`.
+    #[cfg(target_os = "macos")]
+    pub fn capture(planes: &[IORegistryPlane]) -> Result<RegistrySnapshot, Error> {
+        let mut snapshot = RegistrySnapshot::new();
+
+        for &plane in planes {
+            let root = IORegistryEntry::get_root_entry()
+                .ok_or(Error::new(mach::kern_return::KERN_FAILURE))?;
+            let walker = IORegistryWalker::new(
+                plane,
+                io_kit_sys::kIORegistryIterateRecursively,
+                IORegistryTraversal::DepthFirst,
+            )?;
+            let root =
+                SnapshotEntry::build_tree(SnapshotEntry::capture(&root, plane)?, walker, plane)?;
+
+            for (_, entry) in root.walk() {
+                snapshot.capture_superclasses(&entry.class);
+            }
+
+            snapshot.insert(plane, root);
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Parses the output of `ioreg -a -l -p <plane>`.
+    pub fn from_xml(plane: IORegistryPlane, xml: &str) -> Result<RegistrySnapshot, PlistError> {
+        let mut snapshot = RegistrySnapshot::new();
+        snapshot.load_xml(plane, xml)?;
+        Ok(snapshot)
+    }
+
+    /// Parses the output of `ioreg -a -l -p <plane>`, replacing any tree
+    /// already held for `plane`.
+    pub fn load_xml(&mut self, plane: IORegistryPlane, xml: &str) -> Result<(), PlistError> {
+        let mut value = plist::from_xml(xml)?;
+
+        if let PropertyValue::Dictionary(ref mut map) = value {
+            match map.remove(CLASS_HIERARCHY_KEY) {
+                Some(PropertyValue::Dictionary(superclasses)) => {
+                    for (class, superclass) in superclasses {
+                        match superclass {
+                            PropertyValue::String(superclass) => {
+                                self.superclasses.insert(class, superclass);
+                            }
+                            _ => {
+                                return Err(PlistError::new(
+                                    format!("superclass of {} is not a string", class),
+                                    0,
+                                ))
+                            }
+                        }
+                    }
+                }
+                Some(_) => {
+                    return Err(PlistError::new(
+                        format!("{} is not a dictionary", CLASS_HIERARCHY_KEY),
+                        0,
+                    ))
+                }
+                None => {}
+            }
+        }
+
+        let root = SnapshotEntry::from_property_value(&value).ok_or_else(|| {
+            PlistError::new("document is not an ioreg registry entry dictionary", 0)
+        })?;
+
+        self.insert(plane, root);
+        Ok(())
+    }
+
+    /// Serializes the tree of `plane` the way `ioreg -a -l` does, adding the
+    /// recorded class hierarchy.
+    pub fn to_xml(&self, plane: IORegistryPlane) -> Option<String> {
+        let mut value = self.planes.get(&plane)?.to_property_value();
+
+        if let PropertyValue::Dictionary(ref mut map) = value {
+            if !self.superclasses.is_empty() {
+                let superclasses = self
+                    .superclasses
+                    .iter()
+                    .map(|(class, superclass)| (class.clone(), superclass.clone().into()))
+                    .collect::<BTreeMap<_, PropertyValue>>();
+
+                map.insert(CLASS_HIERARCHY_KEY.to_string(), superclasses.into());
+            }
+        }
+
+        Some(plist::to_xml(&value))
+    }
+
+    pub fn insert(&mut self, plane: IORegistryPlane, root: SnapshotEntry) {
+        self.planes.insert(plane, root);
+    }
+
+    pub fn root(&self, plane: IORegistryPlane) -> Option<&SnapshotEntry> {
+        self.planes.get(&plane)
+    }
+
+    pub fn planes(&self) -> impl Iterator<Item = IORegistryPlane> + '_ {
+        self.planes.keys().cloned()
+    }
+
+    pub fn insert_superclass(&mut self, class: &str, superclass: &str) {
+        self.superclasses
+            .insert(class.to_string(), superclass.to_string());
+    }
+
+    pub fn superclass(&self, class: &str) -> Option<&str> {
+        self.superclasses
+            .get(class)
+            .map(|superclass| &superclass[..])
+    }
+
+    /// Returns `true` if `class` is `to` or inherits from it, as far as the
+    /// recorded class hierarchy tells.
+    pub fn conforms_to(&self, class: &str, to: &str) -> bool {
+        let mut class = Some(class);
+
+        // a hierarchy loaded from a file may be cyclic, and an acyclic walk
+        // visits each class at most once
+        for _ in 0..=self.superclasses.len() {
+            match class {
+                Some(name) if name == to => return true,
+                Some(name) => class = self.superclass(name),
+                None => break,
+            }
+        }
+
+        false
+    }
+
+    /// Returns the entries of the service plane that `matching` would match
+    /// on the machine the snapshot was taken from, in depth-first order.
+    pub fn get_matching_entries(&self, matching: &Matching) -> Vec<&SnapshotEntry> {
+        matching::matching_entries(self, matching)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn capture_superclasses(&mut self, class: &str) {
+        let mut class = class.to_string();
+
+        while !self.superclasses.contains_key(&class) {
+            let superclass = unsafe {
+                let result =
+                    IOObjectCopySuperclassForClass(CFString::new(&class).as_concrete_TypeRef());
+
+                if result.is_null() {
+                    break;
+                }
+
+                CFString::wrap_under_create_rule(result).to_string()
+            };
+
+            self.superclasses.insert(class, superclass.clone());
+            class = superclass;
+        }
+    }
+}
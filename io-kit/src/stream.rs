@@ -0,0 +1,437 @@
+//! `Stream`s of IOKit notifications, for async code that does not run a
+//! `CFRunLoop` of its own.
+//!
+//! Notifications are received on a background run loop thread shared by all
+//! streams and queued until the stream is polled, so the streams work with
+//! any executor. Dropping a stream cancels its notification on that thread.
+//!
+//! A stream ends once the notification is torn down on that thread, and a
+//! `ServiceInterestStream` also once its service is terminated.
+
+use std::collections::VecDeque;
+use std::os::raw::c_void;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use core_foundation::base::TCFType;
+use core_foundation::dictionary::CFDictionary;
+use core_foundation::runloop::{
+    kCFRunLoopDefaultMode, CFRunLoop, CFRunLoopSource, CFRunLoopSourceInvalidate,
+};
+use futures_core::Stream;
+use io_kit_sys::ps::power_sources::IOPSNotificationCreateRunLoopSource;
+use io_kit_sys::ret::kIOReturnError;
+
+use crate::base::IOService;
+use crate::error::Error;
+use crate::hid::manager::*;
+use crate::notification::{
+    InterestNotification, InterestType, MatchingNotification, NotificationPort, NotificationType,
+    ServiceMessage,
+};
+use crate::runloop::{AssertSend, BackgroundRunLoop, RunLoopBound};
+
+/// Items queued on the run loop thread until the stream is polled.
+struct Channel<T> {
+    state: Mutex<ChannelState<T>>,
+}
+
+struct ChannelState<T> {
+    items: VecDeque<T>,
+    waker: Option<Waker>,
+    senders: usize,
+    closed: bool,
+}
+
+impl<T> Channel<T> {
+    fn new() -> (Arc<Channel<T>>, Sender<T>) {
+        let channel = Arc::new(Channel {
+            state: Mutex::new(ChannelState {
+                items: VecDeque::new(),
+                waker: None,
+                senders: 1,
+                closed: false,
+            }),
+        });
+        let sender = Sender(channel.clone());
+
+        (channel, sender)
+    }
+
+    /// Wakes the stream to yield what is queued and then end.
+    fn close(&self) {
+        let waker = {
+            let mut state = self.state.lock().unwrap();
+            state.closed = true;
+            state.waker.take()
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    fn poll_recv(&self, cx: &mut Context) -> Poll<Option<T>> {
+        let mut state = self.state.lock().unwrap();
+
+        match state.items.pop_front() {
+            Some(item) => Poll::Ready(Some(item)),
+            None if state.closed => Poll::Ready(None),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// The sending half, owned by the callbacks on the run loop thread. The
+/// channel closes once the last sender is dropped along with them.
+struct Sender<T>(Arc<Channel<T>>);
+
+impl<T> Sender<T> {
+    /// Queues `item`, unless the channel is closed.
+    fn send(&self, item: T) {
+        let waker = {
+            let mut state = self.0.state.lock().unwrap();
+
+            if state.closed {
+                return;
+            }
+
+            state.items.push_back(item);
+            state.waker.take()
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    fn close(&self) {
+        self.0.close()
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Sender<T> {
+        self.0.state.lock().unwrap().senders += 1;
+        Sender(self.0.clone())
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let last = {
+            let mut state = self.0.state.lock().unwrap();
+            state.senders -= 1;
+            state.senders == 0
+        };
+
+        if last {
+            self.0.close();
+        }
+    }
+}
+
+/// Services matching a dictionary as they are published, matched or
+/// terminated, starting with the ones that already match.
+pub struct ServiceMatchingStream {
+    channel: Arc<Channel<IOService>>,
+    // the notification is released before its port
+    _registration: RunLoopBound<(MatchingNotification, NotificationPort)>,
+}
+
+impl ServiceMatchingStream {
+    pub fn new<M: Into<CFDictionary>>(
+        notification_type: NotificationType,
+        matching: M,
+    ) -> Result<ServiceMatchingStream, Error> {
+        let (channel, sender) = Channel::new();
+        let matching = AssertSend(matching.into());
+
+        let registration = BackgroundRunLoop::get().create(move |run_loop| {
+            let matching = matching;
+            let port = NotificationPort::new().ok_or(Error::new(kIOReturnError))?;
+            port.schedule_with_run_loop(run_loop)?;
+
+            let notification =
+                port.add_matching_notification(notification_type, matching.0, move |service| {
+                    sender.send(service)
+                })?;
+
+            Ok::<_, Error>((notification, port))
+        })?;
+
+        Ok(ServiceMatchingStream {
+            channel,
+            _registration: registration,
+        })
+    }
+}
+
+impl Stream for ServiceMatchingStream {
+    type Item = IOService;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<IOService>> {
+        self.channel.poll_recv(cx)
+    }
+}
+
+/// Messages sent by one service for an interest type.
+pub struct ServiceInterestStream {
+    channel: Arc<Channel<ServiceMessage>>,
+    // the notification is released before its port
+    _registration: RunLoopBound<(InterestNotification, NotificationPort)>,
+}
+
+impl ServiceInterestStream {
+    pub fn new(
+        service: &IOService,
+        interest_type: InterestType,
+    ) -> Result<ServiceInterestStream, Error> {
+        // handed to the run loop thread, which releases it once subscribed
+        let service = service.clone();
+
+        let (channel, sender) = Channel::new();
+
+        let registration = BackgroundRunLoop::get().create(move |run_loop| {
+            let port = NotificationPort::new().ok_or(Error::new(kIOReturnError))?;
+            port.schedule_with_run_loop(run_loop)?;
+
+            let notification =
+                port.add_interest_notification(&service, interest_type, move |message| {
+                    sender.send(message);
+
+                    // nothing follows the termination of the service
+                    if message == ServiceMessage::Terminated {
+                        sender.close();
+                    }
+                })?;
+
+            Ok::<_, Error>((notification, port))
+        })?;
+
+        Ok(ServiceInterestStream {
+            channel,
+            _registration: registration,
+        })
+    }
+}
+
+impl Stream for ServiceInterestStream {
+    type Item = ServiceMessage;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<ServiceMessage>> {
+        self.channel.poll_recv(cx)
+    }
+}
+
+/// Devices are given by their registry entries, which can be sent across
+/// threads; open them with `IOHIDDevice::create`.
+pub enum HidDeviceEvent {
+    Attached(IOService),
+    Detached(IOService),
+}
+
+struct HidRegistration {
+    manager: IOHIDManager,
+}
+
+impl Drop for HidRegistration {
+    fn drop(&mut self) {
+        self.manager
+            .unschedule_from_run_loop(&CFRunLoop::get_current(), unsafe { kCFRunLoopDefaultMode });
+        let _ = self.manager.close(kIOHIDManagerOptionNone);
+    }
+}
+
+/// HID devices as they are attached and detached, starting with the ones
+/// already attached.
+pub struct HidDeviceStream {
+    channel: Arc<Channel<HidDeviceEvent>>,
+    _registration: RunLoopBound<HidRegistration>,
+}
+
+impl HidDeviceStream {
+    /// Reports every HID device.
+    pub fn new() -> Result<HidDeviceStream, Error> {
+        HidDeviceStream::create(None)
+    }
+
+    /// Reports the HID devices matching a dictionary of `kIOHIDDevice*Key`
+    /// properties.
+    pub fn with_matching<M: Into<CFDictionary>>(matching: M) -> Result<HidDeviceStream, Error> {
+        HidDeviceStream::create(Some(matching.into()))
+    }
+
+    fn create(matching: Option<CFDictionary>) -> Result<HidDeviceStream, Error> {
+        let (channel, sender) = Channel::new();
+        let matching = AssertSend(matching);
+
+        let registration = BackgroundRunLoop::get().create(move |run_loop| unsafe {
+            let matching = matching;
+            let mut manager = IOHIDManager::new().ok_or(Error::new(kIOReturnError))?;
+            let manager_ref = manager.as_concrete_TypeRef();
+            let detached_sender = sender.clone();
+
+            IOHIDManagerSetDeviceMatching(
+                manager_ref,
+                matching
+                    .0
+                    .as_ref()
+                    .map_or(std::ptr::null(), |matching| matching.as_concrete_TypeRef()),
+            );
+            manager.register_device_matching_callback(move |device| {
+                if let Some(service) = device.get_service() {
+                    sender.send(HidDeviceEvent::Attached(service))
+                }
+            });
+            manager.register_device_removal_callback(move |device| {
+                if let Some(service) = device.get_service() {
+                    detached_sender.send(HidDeviceEvent::Detached(service))
+                }
+            });
+            manager.schedule_with_run_loop(run_loop, kCFRunLoopDefaultMode);
+
+            // unschedules the manager if opening fails
+            let registration = HidRegistration { manager };
+
+            registration.manager.open(kIOHIDManagerOptionNone)?;
+
+            Ok::<_, Error>(registration)
+        })?;
+
+        Ok(HidDeviceStream {
+            channel,
+            _registration: registration,
+        })
+    }
+}
+
+impl Stream for HidDeviceStream {
+    type Item = HidDeviceEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<HidDeviceEvent>> {
+        self.channel.poll_recv(cx)
+    }
+}
+
+struct PowerSourceRegistration {
+    source: CFRunLoopSource,
+    // boxed to keep the address passed as the callback context
+    _sender: Box<Sender<()>>,
+}
+
+impl Drop for PowerSourceRegistration {
+    fn drop(&mut self) {
+        unsafe { CFRunLoopSourceInvalidate(self.source.as_concrete_TypeRef()) }
+    }
+}
+
+/// Yields whenever a power source is attached, detached or changes state;
+/// read the new state with `IOPSCopyPowerSourcesInfo`.
+pub struct PowerSourceStream {
+    channel: Arc<Channel<()>>,
+    _registration: RunLoopBound<PowerSourceRegistration>,
+}
+
+impl PowerSourceStream {
+    pub fn new() -> Result<PowerSourceStream, Error> {
+        let (channel, sender) = Channel::new();
+        let sender = Box::new(sender);
+
+        let registration = BackgroundRunLoop::get().create(move |run_loop| unsafe {
+            let context = &*sender as *const Sender<()> as *mut c_void;
+            let source = IOPSNotificationCreateRunLoopSource(power_source_changed, context);
+
+            if source.is_null() {
+                return Err(Error::new(kIOReturnError));
+            }
+
+            let source: CFRunLoopSource = TCFType::wrap_under_create_rule(source);
+            run_loop.add_source(&source, kCFRunLoopDefaultMode);
+
+            Ok(PowerSourceRegistration {
+                source,
+                _sender: sender,
+            })
+        })?;
+
+        Ok(PowerSourceStream {
+            channel,
+            _registration: registration,
+        })
+    }
+}
+
+impl Stream for PowerSourceStream {
+    type Item = ();
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<()>> {
+        self.channel.poll_recv(cx)
+    }
+}
+
+unsafe extern "C" fn power_source_changed(context: *mut c_void) {
+    let sender = &*(context as *const Sender<()>);
+
+    sender.send(());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+    use std::task::{RawWaker, RawWakerVTable};
+
+    use super::*;
+
+    fn noop_waker() -> Waker {
+        fn clone(_: *const ()) -> RawWaker {
+            RawWaker::new(ptr::null(), &VTABLE)
+        }
+        fn noop(_: *const ()) {}
+
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+
+        unsafe { Waker::from_raw(RawWaker::new(ptr::null(), &VTABLE)) }
+    }
+
+    #[test]
+    fn ends_when_the_senders_are_dropped() {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let (channel, sender) = Channel::new();
+        let other = sender.clone();
+
+        assert_eq!(channel.poll_recv(&mut cx), Poll::Pending);
+
+        sender.send(1);
+        drop(sender);
+        other.send(2);
+
+        assert_eq!(channel.poll_recv(&mut cx), Poll::Ready(Some(1)));
+        assert_eq!(channel.poll_recv(&mut cx), Poll::Ready(Some(2)));
+        assert_eq!(channel.poll_recv(&mut cx), Poll::Pending);
+
+        drop(other);
+
+        assert_eq!(channel.poll_recv(&mut cx), Poll::Ready(None));
+        assert_eq!(channel.poll_recv(&mut cx), Poll::Ready(None));
+    }
+
+    #[test]
+    fn ends_once_closed() {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let (channel, sender) = Channel::new();
+
+        sender.send(1);
+        sender.close();
+        sender.send(2);
+
+        assert_eq!(channel.poll_recv(&mut cx), Poll::Ready(Some(1)));
+        assert_eq!(channel.poll_recv(&mut cx), Poll::Ready(None));
+    }
+}
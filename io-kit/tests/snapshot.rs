@@ -0,0 +1,295 @@
+extern crate io_kit;
+
+use std::collections::BTreeMap;
+
+use io_kit::matching::Matching;
+use io_kit::plane::IORegistryPlane;
+use io_kit::plist;
+use io_kit::property::PropertyValue;
+use io_kit::snapshot::RegistrySnapshot;
+
+const IOREG_USB: &str = include_str!("fixtures/ioreg-usb.xml");
+
+fn load() -> RegistrySnapshot {
+    RegistrySnapshot::from_xml(IORegistryPlane::Service, IOREG_USB).unwrap()
+}
+
+fn load_with_classes() -> RegistrySnapshot {
+    let mut snapshot = load();
+
+    for &(class, superclass) in &[
+        ("IOService", "IORegistryEntry"),
+        ("IOPlatformExpertDevice", "IOService"),
+        ("AppleUSBXHCIPCI", "AppleUSBXHCI"),
+        ("AppleUSBXHCI", "AppleUSBHostController"),
+        ("AppleUSBHostController", "IOService"),
+        ("IOUSBHostDevice", "IOService"),
+        ("IOUSBHostInterface", "IOService"),
+    ] {
+        snapshot.insert_superclass(class, superclass);
+    }
+
+    snapshot
+}
+
+fn matching_names(snapshot: &RegistrySnapshot, matching: &Matching) -> Vec<String> {
+    snapshot
+        .get_matching_entries(matching)
+        .iter()
+        .map(|entry| format!("{}:{}", entry.class, entry.name))
+        .collect()
+}
+
+#[test]
+fn parses_ioreg_output() {
+    let snapshot = load();
+    let root = snapshot.root(IORegistryPlane::Service).unwrap();
+
+    assert_eq!(root.name, "Root");
+    assert_eq!(root.class, "IORegistryEntry");
+    assert_eq!(root.id, 0x1_0000_0000);
+    assert_eq!(root.retain_count, Some(130));
+    assert_eq!(
+        root.walk()
+            .map(|(depth, entry)| (depth, &entry.class[..]))
+            .collect::<Vec<_>>(),
+        vec![
+            (0, "IORegistryEntry"),
+            (1, "IOPlatformExpertDevice"),
+            (2, "AppleUSBXHCIPCI"),
+            (3, "IOUSBHostDevice"),
+            (4, "IOUSBHostInterface"),
+        ]
+    );
+
+    let xhci = root.find(|entry| entry.name == "XHC1").unwrap();
+    assert_eq!(xhci.location.as_deref(), Some("14"));
+    assert_eq!(xhci.busy_state, Some(0));
+    assert_eq!(xhci.state, Some(30));
+    assert_eq!(
+        xhci.properties["compatible"].as_data(),
+        Some(&b"pci8086,9ded\0pciclass,0c0330\0"[..])
+    );
+    assert_eq!(
+        xhci.properties["IOPowerManagement"]
+            .as_dictionary()
+            .unwrap()["CurrentPowerState"],
+        PropertyValue::Integer(3)
+    );
+
+    let device = root.find_by_id(4_294_969_120).unwrap();
+    assert_eq!(device.properties["idVendor"].as_u32(), Some(1452));
+    assert_eq!(device.properties["Built-In"], PropertyValue::Bool(true));
+    assert_eq!(
+        device.properties["IOServiceBusyTime"].as_u64(),
+        Some(u64::MAX)
+    );
+    assert_eq!(device.children.len(), 1);
+    assert!(root.find_by_id(1).is_none());
+}
+
+#[test]
+fn round_trips_ioreg_output() {
+    let snapshot = load();
+    let xml = snapshot.to_xml(IORegistryPlane::Service).unwrap();
+
+    assert_eq!(
+        RegistrySnapshot::from_xml(IORegistryPlane::Service, &xml).unwrap(),
+        snapshot
+    );
+    assert_eq!(plist::from_xml(&xml), plist::from_xml(IOREG_USB));
+    assert!(snapshot.to_xml(IORegistryPlane::USB).is_none());
+}
+
+#[test]
+fn rejects_other_documents() {
+    let xml = plist::to_xml(&PropertyValue::Array(Vec::new()));
+
+    assert!(RegistrySnapshot::from_xml(IORegistryPlane::Service, &xml).is_err());
+    assert!(RegistrySnapshot::from_xml(IORegistryPlane::Service, "<plist>").is_err());
+}
+
+#[test]
+fn round_trips_class_hierarchy() {
+    let snapshot = load_with_classes();
+    let xml = snapshot.to_xml(IORegistryPlane::Service).unwrap();
+    let reloaded = RegistrySnapshot::from_xml(IORegistryPlane::Service, &xml).unwrap();
+
+    assert_eq!(reloaded, snapshot);
+    assert_eq!(reloaded.superclass("AppleUSBXHCIPCI"), Some("AppleUSBXHCI"));
+    assert!(reloaded.conforms_to("AppleUSBXHCIPCI", "IOService"));
+    assert!(!reloaded.conforms_to("IOService", "AppleUSBXHCIPCI"));
+    assert!(!reloaded
+        .root(IORegistryPlane::Service)
+        .unwrap()
+        .properties
+        .contains_key("IOClassHierarchy"));
+}
+
+#[test]
+fn tolerates_cyclic_class_hierarchy() {
+    let mut snapshot = load();
+
+    snapshot.insert_superclass("AppleUSBXHCIPCI", "AppleUSBXHCI");
+    snapshot.insert_superclass("AppleUSBXHCI", "AppleUSBXHCIPCI");
+
+    let xml = snapshot.to_xml(IORegistryPlane::Service).unwrap();
+    let reloaded = RegistrySnapshot::from_xml(IORegistryPlane::Service, &xml).unwrap();
+
+    assert!(reloaded.conforms_to("AppleUSBXHCIPCI", "AppleUSBXHCI"));
+    assert!(!reloaded.conforms_to("AppleUSBXHCIPCI", "IOService"));
+    assert!(matching_names(&reloaded, &Matching::class("IOService")).is_empty());
+}
+
+#[test]
+fn matches_provider_class() {
+    let matching = Matching::class("AppleUSBHostController");
+
+    // ioreg output alone knows nothing of superclasses
+    assert!(matching_names(&load(), &matching).is_empty());
+    assert_eq!(
+        matching_names(&load_with_classes(), &matching),
+        vec!["AppleUSBXHCIPCI:XHC1"]
+    );
+
+    let xml = load_with_classes()
+        .to_xml(IORegistryPlane::Service)
+        .unwrap();
+    let reloaded = RegistrySnapshot::from_xml(IORegistryPlane::Service, &xml).unwrap();
+
+    assert_eq!(
+        matching_names(&reloaded, &Matching::class("IOService")),
+        vec![
+            "IOPlatformExpertDevice:MacBookPro16,1",
+            "AppleUSBXHCIPCI:XHC1",
+            "IOUSBHostDevice:Touch Bar Backlight",
+            "IOUSBHostInterface:Touch Bar Backlight",
+        ]
+    );
+}
+
+#[test]
+fn matches_properties() {
+    let snapshot = load_with_classes();
+
+    assert_eq!(
+        matching_names(&snapshot, &Matching::new().property("idVendor", 1452u32)),
+        vec![
+            "IOUSBHostDevice:Touch Bar Backlight",
+            "IOUSBHostInterface:Touch Bar Backlight",
+        ]
+    );
+    assert_eq!(
+        matching_names(
+            &snapshot,
+            &Matching::new()
+                .property("idVendor", 1452u32)
+                .property("bInterfaceClass", 3u32)
+        ),
+        vec!["IOUSBHostInterface:Touch Bar Backlight"]
+    );
+    assert!(matching_names(&snapshot, &Matching::new().property("idVendor", 1u32)).is_empty());
+
+    // dictionaries are compared as a whole
+    let mut power = BTreeMap::new();
+    power.insert("CurrentPowerState".to_string(), PropertyValue::Integer(3));
+    assert!(matching_names(
+        &snapshot,
+        &Matching::new().property("IOPowerManagement", power)
+    )
+    .is_empty());
+}
+
+#[test]
+fn matches_any_property_dictionary() {
+    let snapshot = load_with_classes();
+
+    let mut interface = BTreeMap::new();
+    interface.insert("bInterfaceClass".to_string(), PropertyValue::Integer(3));
+    let mut xhci = BTreeMap::new();
+    xhci.insert(
+        "IOPCIPrimaryMatch".to_string(),
+        PropertyValue::from("0x9ded8086"),
+    );
+    let matching = Matching::new().properties(interface).properties(xhci);
+
+    let dictionary = matching.to_property_value();
+    let alternatives = dictionary.as_dictionary().unwrap()["IOPropertyMatch"]
+        .as_array()
+        .unwrap();
+    assert_eq!(alternatives.len(), 2);
+
+    assert_eq!(
+        matching_names(&snapshot, &matching),
+        vec![
+            "AppleUSBXHCIPCI:XHC1",
+            "IOUSBHostInterface:Touch Bar Backlight",
+        ]
+    );
+
+    // a property added afterwards narrows the last dictionary only
+    assert_eq!(
+        matching_names(&snapshot, &matching.property("IOProbeScore", 1u32)),
+        vec!["IOUSBHostInterface:Touch Bar Backlight"]
+    );
+}
+
+#[test]
+fn matches_names_paths_and_ids() {
+    let snapshot = load_with_classes();
+
+    // device tree names come from the `compatible` data
+    assert_eq!(
+        matching_names(&snapshot, &Matching::new().name("pciclass,0c0330")),
+        vec!["AppleUSBXHCIPCI:XHC1"]
+    );
+    assert_eq!(
+        matching_names(
+            &snapshot,
+            &Matching::new().name("XHC2").name("MacBookPro16,1")
+        ),
+        vec!["IOPlatformExpertDevice:MacBookPro16,1"]
+    );
+    assert_eq!(
+        matching_names(
+            &snapshot,
+            &Matching::new().path("IOService:/MacBookPro16,1/XHC1@14/")
+        ),
+        vec!["AppleUSBXHCIPCI:XHC1"]
+    );
+    assert_eq!(
+        matching_names(&snapshot, &Matching::new().path("IOService:/")),
+        vec!["IORegistryEntry:Root"]
+    );
+    assert_eq!(
+        matching_names(&snapshot, &Matching::new().registry_entry_id(4_294_969_153)),
+        vec!["IOUSBHostInterface:Touch Bar Backlight"]
+    );
+}
+
+#[test]
+fn matches_providers_and_ancestors() {
+    let snapshot = load_with_classes();
+    let usb = Matching::new().property("idVendor", 1452u32);
+
+    assert_eq!(
+        matching_names(&snapshot, &Matching::new().location(usb.clone())),
+        vec!["IOUSBHostInterface:Touch Bar Backlight"]
+    );
+    assert_eq!(
+        matching_names(
+            &snapshot,
+            &usb.clone()
+                .parent(Matching::class("AppleUSBHostController"))
+        ),
+        vec![
+            "IOUSBHostDevice:Touch Bar Backlight",
+            "IOUSBHostInterface:Touch Bar Backlight",
+        ]
+    );
+    assert!(matching_names(
+        &snapshot,
+        &usb.location(Matching::class("IOPlatformExpertDevice"))
+    )
+    .is_empty());
+}